@@ -0,0 +1,55 @@
+use costoflife::{self, TxRecord};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use std::str::FromStr;
+
+/// The number of records of the ledger
+const RECORDS: usize = 10_000;
+
+/// Build a ledger of records with different amounts, lifetimes and start dates
+fn ledger() -> Vec<TxRecord> {
+    let lifetimes = ["1d", "1w4x", "1m12x", "1y", "30d", "2w26x", "3m", "1m"];
+    (0..RECORDS)
+        .map(|i| {
+            let spec = format!(
+                "Expense {} {}.{:02}€ {} {}",
+                i,
+                1 + i % 997,
+                i % 100,
+                lifetimes[i % lifetimes.len()],
+                costoflife::date(1 + (i % 28) as u32, 1 + (i % 12) as u32, 2021).format("%d%m%y")
+            );
+            TxRecord::from_str(&spec).unwrap()
+        })
+        .collect()
+}
+
+fn cost_of_life(c: &mut Criterion) {
+    let on = costoflife::date(15, 6, 2021);
+    // the records are never queried, so their clones have no cached per diem
+    let records = ledger();
+    // the per diem is cached on the first query
+    let cached = records.clone();
+    let total = costoflife::cost_of_life(cached.iter(), &on);
+    // the cached values are the same as the computed ones
+    assert_eq!(costoflife::cost_of_life(cached.iter(), &on), total);
+    assert!(records
+        .iter()
+        .zip(cached.iter())
+        .all(|(tx, ctx)| tx.clone().per_diem_raw() == ctx.per_diem_raw()));
+
+    let mut group = c.benchmark_group("cost_of_life_10k");
+    group.bench_function("uncached", |b| {
+        b.iter_batched(
+            || records.clone(),
+            |txs| costoflife::cost_of_life(txs.iter(), &on),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| costoflife::cost_of_life(cached.iter(), &on))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cost_of_life);
+criterion_main!(benches);
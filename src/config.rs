@@ -0,0 +1,151 @@
+use ::costoflife::{currency_code, currency_symbol, CostOfLifeError, DEFAULT_CURRENCY};
+use chrono::format::{Item, StrftimeItems};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// The name of the config file in the config dir
+pub const CONFIG_FILENAME: &str = "config.toml";
+/// The max number of decimals of the amounts
+const MAX_SCALE: usize = 10;
+
+/// The defaults of the command line client
+///
+/// All the fields are optional in the config file,
+/// the missing ones get the default value
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// the currency of the totals, an ISO 4217 code or a symbol
+    pub currency: String,
+    /// the format of the dates, in the strftime syntax
+    pub date_format: String,
+    /// the number of decimals of the amounts
+    pub scale: usize,
+    /// the glyph of the progress bars
+    pub progress: char,
+    /// the data file, used when no file is set with `-f`
+    pub data_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            currency: DEFAULT_CURRENCY.to_string(),
+            date_format: "%Y-%m-%d".to_string(),
+            scale: 2,
+            progress: '▮',
+            data_file: None,
+        }
+    }
+}
+
+impl Config {
+    /// Parse a config from its toml representation
+    ///
+    /// The currency symbols are turned into their ISO 4217 code
+    pub fn from_toml(s: &str) -> Result<Config, CostOfLifeError> {
+        let mut config: Config =
+            toml::from_str(s).map_err(|e| CostOfLifeError::GenericError(e.to_string()))?;
+        config.currency = currency_code(config.currency.trim()).to_string();
+        if config.currency.len() != 3 || !config.currency.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(CostOfLifeError::GenericError(format!(
+                "invalid currency, use an ISO 4217 code or a known symbol: {}",
+                config.currency
+            )));
+        }
+        if StrftimeItems::new(&config.date_format).any(|i| matches!(i, Item::Error)) {
+            return Err(CostOfLifeError::InvalidDateFormat(format!(
+                "invalid date format: {}",
+                config.date_format
+            )));
+        }
+        if config.scale > MAX_SCALE {
+            return Err(CostOfLifeError::GenericError(format!(
+                "the scale should be at most {}: {}",
+                MAX_SCALE, config.scale
+            )));
+        }
+        Ok(config)
+    }
+    /// Read a config from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the toml file
+    pub fn load(path: &Path) -> Result<Config, CostOfLifeError> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| CostOfLifeError::GenericError(format!("{:?}: {}", path, e)))?;
+        Config::from_toml(&data)
+            .map_err(|e| CostOfLifeError::GenericError(format!("{:?}: {}", path, e)))
+    }
+    /// Get the symbol of the currency of the totals
+    pub fn currency_symbol(&self) -> &str {
+        currency_symbol(&self.currency)
+    }
+}
+
+lazy_static! {
+    static ref CURRENT: RwLock<Config> = RwLock::new(Config::default());
+}
+
+/// Set the config used by the client
+pub fn set(config: Config) {
+    *CURRENT.write().unwrap() = config;
+}
+
+/// Get the config used by the client, the default one if none is set
+pub fn get() -> Config {
+    CURRENT.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        // all the fields
+        let config = Config::from_toml(
+            r##"
+            currency = "$"
+            date_format = "%d/%m/%Y"
+            scale = 0
+            progress = "#"
+            data_file = "/tmp/costoflife.data.txt"
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config.currency, "USD");
+        assert_eq!(config.currency_symbol(), "$");
+        assert_eq!(config.date_format, "%d/%m/%Y");
+        assert_eq!(config.scale, 0);
+        assert_eq!(config.progress, '#');
+        assert_eq!(
+            config.data_file,
+            Some(PathBuf::from("/tmp/costoflife.data.txt"))
+        );
+        // the missing fields are the defaults
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+        let config = Config::from_toml("currency = \"GBP\"").unwrap();
+        assert_eq!(config.currency_symbol(), "£");
+        assert_eq!(config.scale, Config::default().scale);
+        // invalid configs
+        let tests = vec![
+            "currency = \"euro\"",
+            "date_format = \"%Q\"",
+            "scale = 11",
+            "scale = -1",
+            "progress = \"##\"",
+            "colour = true",
+            "currency = ",
+        ];
+        for spec in tests {
+            assert!(Config::from_toml(spec).is_err(), "{}", spec);
+        }
+        // files
+        assert!(Config::load(Path::new("./testdata/missing.toml")).is_err());
+    }
+}
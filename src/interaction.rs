@@ -1,9 +1,34 @@
-use ::costoflife::{parse_amount, today, CostOfLifeError, TxRecord};
+use ::costoflife::ledger::DataStore;
+use ::costoflife::{
+    currency_symbol, format_hashtag, has_generic_currency, parse_amount, today, CostOfLifeError,
+    TxRecord,
+};
 use dialoguer::console::Term;
-use dialoguer::{theme::ColorfulTheme, Confirm, Input, Password, Select};
+use dialoguer::theme::{ColorfulTheme, SimpleTheme, Theme};
+use dialoguer::{Confirm, Input, Select};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use Feat::*;
-use PolarAnswer::*;
+
+/// Use a plain theme, without colors, for the prompts
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switch the prompts to a plain theme, without colors
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+    if plain {
+        dialoguer::console::set_colors_enabled(false);
+        dialoguer::console::set_colors_enabled_stderr(false);
+    }
+}
+
+/// Get the theme for the prompts
+pub fn theme() -> Box<dyn Theme> {
+    match PLAIN.load(Ordering::Relaxed) {
+        true => Box::new(SimpleTheme),
+        false => Box::new(ColorfulTheme::default()),
+    }
+}
 
 #[derive(PartialEq)]
 pub enum PolarAnswer {
@@ -42,10 +67,29 @@ impl Feat {
     }
 }
 
+/// Tells if there is a user to answer the prompts
+///
+/// the prompts are shown on stderr, when it is not a terminal
+/// (eg. in scripts or CI) the prompts would fail
+pub fn is_interactive() -> bool {
+    dialoguer::console::user_attended_stderr()
+}
+
+/// warn that the amount of a spec string with the generic
+/// currency sign (eg. `10¤`) is in the default currency
+pub fn warn_generic_currency(spec: &str, currency: &str) {
+    if has_generic_currency(spec) {
+        eprintln!(
+            "warning: the generic currency sign ¤ is not a currency, the amount is in {}",
+            currency
+        );
+    }
+}
+
 /// shortcut for Confirm
 pub fn confirm(q: &str, def: PolarAnswer) -> PolarAnswer {
     PolarAnswer::from_bool(
-        Confirm::with_theme(&ColorfulTheme::default())
+        Confirm::with_theme(&*theme())
             .with_prompt(q)
             .default(def.to_bool())
             .interact()
@@ -53,18 +97,19 @@ pub fn confirm(q: &str, def: PolarAnswer) -> PolarAnswer {
     )
 }
 
-/// shortcut for Input
-pub fn input(q: &str, empty: Feat) -> String {
-    Input::with_theme(&ColorfulTheme::default())
+/// shortcut for Input, the initial text can be edited
+pub fn input(q: &str, initial: &str, empty: Feat) -> String {
+    Input::with_theme(&*theme())
         .with_prompt(q)
+        .with_initial_text(initial)
         .allow_empty(empty.to_bool())
-        .interact()
+        .interact_text()
         .unwrap()
 }
 
 /// shortcut for Select optional input
 pub fn select_opt<'a, T: ?Sized>(q: &str, opts: Vec<(&'a str, &'a T)>) -> Option<&'a T> {
-    match Select::with_theme(&ColorfulTheme::default())
+    match Select::with_theme(&*theme())
         .with_prompt(q)
         .items(
             &opts
@@ -81,57 +126,92 @@ pub fn select_opt<'a, T: ?Sized>(q: &str, opts: Vec<(&'a str, &'a T)>) -> Option
     }
 }
 
-/// Select an item
-pub fn select<'a, T: ?Sized>(q: &str, opts: Vec<(&'a str, &'a T)>) -> &'a T {
-    opts[Select::with_theme(&ColorfulTheme::default())
-        .with_prompt(q)
-        .items(
-            &opts
-                .iter()
-                .map(|(l, _v)| l.to_string())
-                .collect::<Vec<String>>(),
-        )
-        .default(0)
-        .interact_on(&Term::stdout())
-        .unwrap()]
-    .1
-}
-
 /// Show the options
 pub fn menu() -> Option<String> {
-    // ask for the quality
-    match select_opt(
+    select_opt(
         "hello there, what's up? esc/q to quit",
         vec![
             ("Summary", "summary"),
-            ("Tags", "agenda"),
-            ("New Tx", "today"),
+            ("Tags", "tags"),
+            ("New Tx", "new"),
+            ("Edit Tx", "edit"),
         ],
-    ) {
-        Some(x) => Some(x.to_string()),
-        _ => None,
-    }
+    )
+    .map(|x| x.to_string())
 }
 
-pub fn new_tx() -> Result<TxRecord, CostOfLifeError> {
-    let name = input("What it is it about?", NonEmpty);
-    // amount
-    let mut amount = parse_amount("0.0").unwrap();
-    loop {
-        let v = input("how much does it cost?", NonEmpty);
-        amount = match parse_amount(&v) {
-            Some(a) => a,
-            None => continue,
-        }
-    }
-    // tags
-    let mut tags: Vec<String> = Vec::new();
-    while Yes == confirm("add a tag?", No) {
-        tags.push(input("tag label: ", NonEmpty));
-    }
-    let starts_on = today();
+/// Prompt for a new tx record
+pub fn new_tx(ds: &DataStore) -> Result<TxRecord, CostOfLifeError> {
+    tx_prompts(ds, None)
+}
+
+/// Prompt for the changes to an existing tx record
+///
+/// The prompts are pre-filled with the values of the record
+pub fn edit_tx(ds: &DataStore, tx: &TxRecord) -> Result<TxRecord, CostOfLifeError> {
+    tx_prompts(ds, Some(tx))
+}
 
-    //
-    //TxRecord::from(name, tags, amount, starts_on, Lifetime::SingleDay, )
-    Err(CostOfLifeError::GenericError("Not implemented".to_owned()))
+/// Ask for the tx record fields and parse them as a spec string
+///
+/// a plain amount gets the currency of the record (or the currency of the config),
+/// a percentage of another transaction is resolved with the datastore
+fn tx_prompts(ds: &DataStore, tx: Option<&TxRecord>) -> Result<TxRecord, CostOfLifeError> {
+    let name = input(
+        "What is it about?",
+        tx.map_or("", |t| t.get_name()),
+        NonEmpty,
+    );
+    // amount, the currency is added if missing
+    let currency = crate::config::get().currency;
+    let symbol = currency_symbol(tx.map_or(&currency, |t| t.get_currency()));
+    let amount = input(
+        "How much does it cost?",
+        &tx.map_or(String::new(), |t| format!("{}{}", t.get_amount(), symbol)),
+        NonEmpty,
+    );
+    let amount = match parse_amount(amount.trim()) {
+        Some(a) => format!("{}{}", a, symbol),
+        None => amount,
+    };
+    // lifetime
+    let lifetime = input(
+        "For how long? (eg. 1m12x)",
+        &tx.map_or("1d".to_string(), |t| t.get_lifetime().to_string()),
+        NonEmpty,
+    );
+    // start date
+    let starts_on = input(
+        "Since when? (ddmmyy)",
+        &tx.map_or(today(), |t| t.get_starts_on())
+            .format("%d%m%y")
+            .to_string(),
+        NonEmpty,
+    );
+    // tags
+    let tags = input(
+        "Tags (eg. #home .bills)",
+        &tx.map_or(Vec::new(), |t| t.get_tags())
+            .iter()
+            .map(|t| format_hashtag(t))
+            .collect::<Vec<String>>()
+            .join(" "),
+        Empty,
+    );
+    // metadata
+    let meta = input(
+        "Metadata (eg. vendor=Amazon)",
+        &tx.map_or(Vec::new(), |t| t.get_metadata())
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join(" "),
+        Empty,
+    );
+    let spec = format!(
+        "{} {} {} {} {} {}",
+        name, amount, lifetime, starts_on, tags, meta
+    );
+    warn_generic_currency(&spec, &currency);
+    ds.parse_str_in(&spec, &today(), &currency)
 }
@@ -1,19 +1,178 @@
-use ::costoflife::{self, TxRecord};
-use bigdecimal::{BigDecimal, ToPrimitive};
-use chrono::NaiveDate;
+use crate::{CostOfLifeError, Lifetime, Period, TxRecord};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use simsearch::SimSearch;
-use std::collections::HashMap;
-use std::fs::File;
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{File, OpenOptions};
 use std::io::{self, BufRead, LineWriter, Write};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref RE_PERCENTAGE: Regex = Regex::new(r#"(\d+(?:\.\d+)?)% of "([^"]+)""#).unwrap();
+}
+
+/// A change made to the datastore
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditOp {
+    Insert,
+    Remove,
+    /// the record with the `replaced` hash has been replaced
+    Update {
+        replaced: blake3::Hash,
+    },
+}
+
+/// An entry of the datastore audit log
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// the change
+    pub op: AuditOp,
+    /// the hash of the record inserted, removed or updated
+    pub hash: blake3::Hash,
+    /// when the change was made
+    pub at: DateTime<FixedOffset>,
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.op {
+            AuditOp::Insert => write!(f, "{}::insert::{}", self.at.to_rfc3339(), self.hash),
+            AuditOp::Remove => write!(f, "{}::remove::{}", self.at.to_rfc3339(), self.hash),
+            AuditOp::Update { replaced } => write!(
+                f,
+                "{}::update::{}::{}",
+                self.at.to_rfc3339(),
+                self.hash,
+                replaced
+            ),
+        }
+    }
+}
+
+/// An issue found linting the records of a data file
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintIssue {
+    /// the line number, starting from 1
+    pub line: usize,
+    /// what is wrong with the line
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The number of years in the future after which a start date is suspicious
+const LINT_MAX_YEARS_AHEAD: i32 = 10;
+
+/// A filter on the tags of the records
+///
+/// A record matches if it has at least one of the included tags
+/// (any record if there are none) and none of the excluded ones.
+/// The tags are compared by their slug, like [`TxRecord::has_tag`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl TagFilter {
+    /// Build a filter from the tags to include and to exclude
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - The tags a record should have one of
+    /// * `exclude` - The tags a record should not have
+    pub fn new(include: &[&str], exclude: &[&str]) -> Self {
+        let slugs = |tags: &[&str]| {
+            tags.iter()
+                .map(|t| crate::tag_slug(t))
+                .collect::<Vec<String>>()
+        };
+        TagFilter {
+            include: slugs(include),
+            exclude: slugs(exclude),
+        }
+    }
+    /// Tells if the filter lets all the records through
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+    /// Tells if a record passes the filter
+    pub fn matches(&self, tx: &TxRecord) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|t| tx.has_tag(t)))
+            && !self.exclude.iter().any(|t| tx.has_tag(t))
+    }
+}
+
+impl fmt::Display for TagFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tags = self
+            .include
+            .iter()
+            .map(|t| format!("#{}", t))
+            .chain(self.exclude.iter().map(|t| format!("-#{}", t)))
+            .collect::<Vec<String>>();
+        write!(f, "{}", tags.join(" "))
+    }
+}
+
+/// An owned view of the whole datastore that can be serialized
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerSnapshot {
+    /// when the snapshot was taken
+    pub generated_at: DateTime<FixedOffset>,
+    /// the version of the library that took the snapshot
+    pub version: String,
+    /// the number of records
+    pub count: usize,
+    /// the records, sorted by start date and name
+    pub records: Vec<TxRecord>,
+}
+
+/// Remove the null values from a json value
+///
+/// The null values are dropped from objects and arrays
+fn strip_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(m) => m
+            .into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k, strip_nulls(v)))
+            .collect(),
+        serde_json::Value::Array(a) => a
+            .into_iter()
+            .filter(|v| !v.is_null())
+            .map(strip_nulls)
+            .collect(),
+        v => v,
+    }
+}
 
 /// A simple datastore that can persist data on file
 ///
 pub struct DataStore {
     data: HashMap<blake3::Hash, TxRecord>,
     index: SimSearch<blake3::Hash>,
+    cache: Option<Mutex<HashMap<NaiveDate, f32>>>,
+    audit: Vec<AuditEntry>,
+}
+impl Default for DataStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl DataStore {
     /// Initialize an empty datastore
     ///
@@ -21,6 +180,18 @@ impl DataStore {
         DataStore {
             data: HashMap::new(),
             index: SimSearch::new(),
+            cache: None,
+            audit: Vec::new(),
+        }
+    }
+    /// Initialize an empty datastore that memoizes
+    /// the cost of life by date
+    ///
+    /// the cache is cleared every time the records change
+    pub fn with_cache() -> DataStore {
+        DataStore {
+            cache: Some(Mutex::new(HashMap::new())),
+            ..DataStore::new()
         }
     }
     /// Load the datastore with the records found
@@ -29,42 +200,228 @@ impl DataStore {
         // read path
         if let Ok(lines) = DataStore::read_lines(log_file) {
             for line in lines {
-                let record = line?;
-                if let Ok(tx) = TxRecord::from_string_record(&record) {
-                    let th = Self::hash(&tx);
-                    // index for search the title and the tags
-                    self.index.insert(
-                        th,
-                        &format!("{} {}", tx.get_name(), tx.get_tags().join(" ")),
-                    );
-                    // here is the move
-                    self.data.insert(th, tx);
-                }
+                self.load_record(&line?);
             }
         }
         Ok(())
     }
+    /// Load the datastore with the records found in several files
+    ///
+    /// The records are merged, a record found in more than one
+    /// file (same hash) is loaded only once, the last one wins
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths of the files, as for [`DataStore::load`]
+    pub fn load_many(&mut self, paths: &[&Path]) -> Result<(), std::io::Error> {
+        for p in paths {
+            self.load(p)?;
+        }
+        Ok(())
+    }
+    /// Load the datastore with the records found
+    /// in a string, one record per line
+    ///
+    /// it is the same format of the file used by [`DataStore::load`],
+    /// the lines that are not valid records are skipped
+    pub fn load_str(&mut self, data: &str) {
+        data.lines().for_each(|record| self.load_record(record));
+    }
+    /// Check the records of a data file, in the format used by [`DataStore::load`]
+    ///
+    /// Unlike loading, that skips the invalid lines, it reports an issue for
+    /// every line that cannot be parsed (including zero amounts), that has
+    /// an empty name, a start date far in the future, a recording
+    /// time in the future or that is a duplicate of a previous line.
+    /// Empty lines are ignored
+    pub fn lint_str(data: &str) -> Vec<LintIssue> {
+        let mut issues = Vec::new();
+        let mut seen: HashMap<blake3::Hash, usize> = HashMap::new();
+        let now = crate::now_local();
+        let max_start = crate::today()
+            .with_year(crate::today().year() + LINT_MAX_YEARS_AHEAD)
+            .unwrap_or_else(crate::today);
+        for (i, record) in data.lines().enumerate() {
+            let line = i + 1;
+            let mut report = |message: String| issues.push(LintIssue { line, message });
+            if record.trim().is_empty() {
+                continue;
+            }
+            let tx = match TxRecord::from_string_record(record) {
+                Ok(tx) => tx,
+                Err(e) => {
+                    report(e.to_string());
+                    continue;
+                }
+            };
+            if tx.get_name().is_empty() {
+                report("the name is empty".to_string());
+            }
+            if tx.get_starts_on() > max_start {
+                report(format!(
+                    "the start date is more than {} years in the future: {}",
+                    LINT_MAX_YEARS_AHEAD,
+                    tx.get_starts_on()
+                ));
+            }
+            if *tx.get_recorded_at() > now {
+                report(format!(
+                    "the recording time is in the future: {}",
+                    tx.get_recorded_at_rfc3339()
+                ));
+            }
+            match seen.get(&Self::hash(&tx)) {
+                Some(first) => report(format!("duplicate of line {}", first)),
+                None => {
+                    seen.insert(Self::hash(&tx), line);
+                }
+            }
+        }
+        issues
+    }
+    /// Load a single record line, skipping it if it is not valid
+    fn load_record(&mut self, record: &str) {
+        if let Ok(tx) = TxRecord::from_string_record(record) {
+            let th = Self::hash(&tx);
+            // index for search the title, the tags and the metadata
+            self.index.insert(th, &Self::index_text(&tx));
+            // here is the move
+            self.data.insert(th, tx);
+            self.invalidate();
+        }
+    }
     /// Persist the datastore to disk, overwriting existing files
     ///
-    /// The order of the item saved is random
+    /// The records are saved in their canonical form, sorted by
+    /// start date, name and record, so that the file is diff friendly
     pub fn save(&self, log_file: &Path) -> Result<(), std::io::Error> {
         let mut file = LineWriter::new(File::create(log_file)?);
-        self.data.iter().for_each(|v| {
-            file.write(v.1.to_string_record().as_bytes()).ok();
-        });
+        let mut records = self
+            .data
+            .values()
+            .map(|tx| (tx.get_starts_on(), tx.get_name(), tx.to_string_record()))
+            .collect::<Vec<(NaiveDate, &str, String)>>();
+        records.sort();
+        for (_, _, r) in records {
+            file.write_all(r.as_bytes())?;
+        }
         file.flush()?;
         Ok(())
     }
+    /// Export the datastore to csv
+    ///
+    /// The amounts are plain numbers, the currency
+    /// is exported in its own column.
+    /// The records are sorted by start date and name
+    pub fn to_csv(&self) -> String {
+        let mut txs = self.data.values().collect::<Vec<&TxRecord>>();
+        txs.sort_by(|a, b| {
+            (a.get_starts_on(), a.get_name()).cmp(&(b.get_starts_on(), b.get_name()))
+        });
+        let mut csv =
+            String::from("name,amount,currency,lifetime,starts_on,ends_on,per_diem,tags\n");
+        txs.iter().for_each(|tx| {
+            let row = [
+                csv_field(tx.get_name()),
+                tx.get_amount_plain(),
+                tx.get_currency().to_string(),
+                tx.get_lifetime().to_string(),
+                tx.get_starts_on().to_string(),
+                tx.get_ends_on().to_string(),
+                tx.per_diem().to_string(),
+                csv_field(&tx.get_tags().join(" ")),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        });
+        csv
+    }
     /// Retrieve the cost of life for a date
     ///
+    /// if the cache is enabled the value is computed only
+    /// the first time a date is requested
     pub fn cost_of_life(&self, d: &NaiveDate) -> f32 {
-        costoflife::cost_of_life(self.data.values(), d)
-            .to_f32()
-            .unwrap()
+        match &self.cache {
+            Some(cache) => *cache
+                .lock()
+                .unwrap()
+                .entry(*d)
+                .or_insert_with(|| self.compute_cost_of_life(d)),
+            None => self.compute_cost_of_life(d),
+        }
+    }
+    /// Retrieve the cost of life for a date
+    /// choosing if the records are active on their end date
+    ///
+    /// the cache is used only for the default, inclusive, definition
+    /// see [`crate::cost_of_life_with`] for the difference
+    pub fn cost_of_life_with(&self, d: &NaiveDate, end_inclusive: bool) -> f32 {
+        match end_inclusive {
+            true => self.cost_of_life(d),
+            false => crate::to_f32_safe(&crate::cost_of_life_with(self.data.values(), d, false)),
+        }
+    }
+    /// Retrieve the cost of life for a date over a period
+    /// (eg. the monthly cost of life), the cache is not used
+    ///
+    pub fn cost_of_life_per(&self, d: &NaiveDate, period: &Period) -> f32 {
+        crate::to_f32_safe(&crate::cost_of_life_per(self.data.values(), d, period))
+    }
+    /// Retrieve the cost of life for a date over a period
+    /// of the records that pass a tag filter, the cache is not used
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to compute the cost of life for
+    /// * `period` - The period to compute the cost of life over
+    /// * `filter` - The tag filter the records must pass
+    ///
+    pub fn cost_of_life_filtered(&self, d: &NaiveDate, period: &Period, filter: &TagFilter) -> f32 {
+        crate::to_f32_safe(&crate::cost_of_life_per(
+            self.data.values().filter(|tx| filter.matches(tx)),
+            d,
+            period,
+        ))
+    }
+    /// Retrieve the gross expenses for a date, that is the cost of life
+    /// of the records with a positive amount
+    ///
+    pub fn expense_total(&self, d: &NaiveDate) -> f32 {
+        crate::to_f32_safe(&crate::cost_of_life(
+            self.data
+                .values()
+                .filter(|tx| tx.get_amount() > BigDecimal::zero()),
+            d,
+        ))
+    }
+    /// Retrieve the gross income for a date, that is the cost of life
+    /// of the records with a negative amount (eg. refunds)
+    ///
+    /// The total is negative, so that the sum with [`DataStore::expense_total`]
+    /// is the net [`DataStore::cost_of_life`]
+    pub fn income_total(&self, d: &NaiveDate) -> f32 {
+        crate::to_f32_safe(&crate::cost_of_life(
+            self.data
+                .values()
+                .filter(|tx| tx.get_amount() < BigDecimal::zero()),
+            d,
+        ))
+    }
+    /// Compute the cost of life for a date, bypassing the cache
+    fn compute_cost_of_life(&self, d: &NaiveDate) -> f32 {
+        crate::to_f32_safe(&crate::cost_of_life(self.data.values(), d))
+    }
+    /// Clear the cost of life cache, if enabled
+    fn invalidate(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().clear();
+        }
     }
     /// Perform a search for a string in tags and transaction name
     ///
+    /// The totals, the end dates and the progress are the ones of today
     pub fn search(&self, pattern: &str) -> Vec<(String, f32, f32, String, String, f32, String)> {
+        let today = crate::today();
         self.index
             .search(pattern)
             .iter()
@@ -72,39 +429,210 @@ impl DataStore {
                 let tx = self.data.get(h).unwrap();
                 (
                     tx.get_name().to_string(),
-                    tx.get_amount_total().to_f32().unwrap(),
-                    tx.per_diem().to_f32().unwrap(),
+                    crate::to_f32_safe(&tx.get_amount_total_at(&today)),
+                    crate::to_f32_safe(&tx.per_diem_at(&today)),
                     tx.get_starts_on().to_string(),
-                    tx.get_ends_on().to_string(),
-                    tx.get_progress(None),
+                    tx.get_ends_on_at(&today).to_string(),
+                    tx.get_progress(Some(today)),
                     tx.get_tags().join("/"),
                 )
             })
             .collect()
     }
+    /// Find the records with exactly the given name
+    ///
+    /// The comparison is case sensitive, the names are not unique
+    /// so all the matches are returned, sorted by start date
+    pub fn find_by_name(&self, name: &str) -> Vec<&TxRecord> {
+        let mut s = self
+            .data
+            .values()
+            .filter(|tx| tx.get_name() == name)
+            .collect::<Vec<&TxRecord>>();
+        s.sort_by_key(|tx| tx.get_starts_on());
+        s
+    }
+    /// Find the gaps in the history of a recurring expense
+    ///
+    /// The records with exactly the given name are sorted by start date,
+    /// a gap is a range of days, bounds included, that is not covered
+    /// between the end of a record and the start of the next one.
+    /// Overlapping records are not reported, a record that repeats
+    /// until cancelled covers all the records that start after it
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the records
+    pub fn find_gaps(&self, name: &str) -> Vec<(NaiveDate, NaiveDate)> {
+        let mut gaps = Vec::new();
+        let mut covered: Option<NaiveDate> = None;
+        let txs = self.find_by_name(name);
+        let last_start = match txs.last() {
+            Some(tx) => tx.get_starts_on(),
+            None => return gaps,
+        };
+        txs.iter().for_each(|tx| {
+            if let Some(last) = covered {
+                if tx.get_starts_on() > last + Duration::days(1) {
+                    gaps.push((
+                        last + Duration::days(1),
+                        tx.get_starts_on() - Duration::days(1),
+                    ));
+                }
+            }
+            let ends_on = tx.get_ends_on_at(&last_start);
+            covered = Some(covered.map_or(ends_on, |last| max(last, ends_on)));
+        });
+        gaps
+    }
+    /// Find the records with an amount within a range, bounds included
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum amount
+    /// * `max` - The maximum amount
+    /// * `total` - Whether to compare the total amount (the amount times the
+    ///   lifetime repeats, see [`TxRecord::get_amount_total`]) instead of the amount
+    ///
+    /// The records are sorted by the compared amount, then by name.
+    /// The amounts are compared regardless of their currency
+    pub fn between_amounts(
+        &self,
+        min: &BigDecimal,
+        max: &BigDecimal,
+        total: bool,
+    ) -> Vec<&TxRecord> {
+        let amount = |tx: &TxRecord| match total {
+            true => tx.get_amount_total(),
+            false => tx.get_amount(),
+        };
+        let mut s = self
+            .data
+            .values()
+            .filter(|tx| {
+                let a = amount(tx);
+                *min <= a && a <= *max
+            })
+            .collect::<Vec<&TxRecord>>();
+        s.sort_by(|a, b| (amount(a), a.get_name()).cmp(&(amount(b), b.get_name())));
+        s
+    }
     /// Compile a summary of the active costs, returning a tuple with
     /// (title, total amount, cost per day, percentage payed)
-    pub fn summary(&self, d: &NaiveDate) -> Vec<(String, f32, f32, f32)> {
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to filter for active transactions
+    /// * `limit` - An optional maximum number of results, applied after sorting
+    ///
+    pub fn summary(&self, d: &NaiveDate, limit: Option<usize>) -> Vec<(String, f32, f32, f32)> {
+        self.summary_with(d, limit, true)
+    }
+    /// Same as [`DataStore::summary`], choosing if the
+    /// records are active on their end date
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to filter for active transactions
+    /// * `limit` - An optional maximum number of results, applied after sorting
+    /// * `end_inclusive` - Whether a record is active on its end date
+    ///
+    /// with `end_inclusive` set to false the records ending on `d` are excluded
+    ///
+    pub fn summary_with(
+        &self,
+        d: &NaiveDate,
+        limit: Option<usize>,
+        end_inclusive: bool,
+    ) -> Vec<(String, f32, f32, f32)> {
+        self.summarize(d, limit, end_inclusive, &Period::Day, &TagFilter::default())
+    }
+    /// Same as [`DataStore::summary`], with the cost of the
+    /// records over a period instead of the per diem
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to filter for active transactions
+    /// * `limit` - An optional maximum number of results, applied after sorting
+    /// * `period` - The period to compute the cost of the records over
+    ///
+    pub fn summary_per(
+        &self,
+        d: &NaiveDate,
+        limit: Option<usize>,
+        period: &Period,
+    ) -> Vec<(String, f32, f32, f32)> {
+        self.summarize(d, limit, true, period, &TagFilter::default())
+    }
+    /// Same as [`DataStore::summary_per`], with only the
+    /// records that pass a tag filter
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to filter for active transactions
+    /// * `limit` - An optional maximum number of results, applied after sorting
+    /// * `period` - The period to compute the cost of the records over
+    /// * `filter` - The tag filter the records must pass
+    ///
+    pub fn summary_filtered(
+        &self,
+        d: &NaiveDate,
+        limit: Option<usize>,
+        period: &Period,
+        filter: &TagFilter,
+    ) -> Vec<(String, f32, f32, f32)> {
+        self.summarize(d, limit, true, period, filter)
+    }
+    /// Build the summary of the active records that pass
+    /// a tag filter, with their cost over a period
+    fn summarize(
+        &self,
+        d: &NaiveDate,
+        limit: Option<usize>,
+        end_inclusive: bool,
+        period: &Period,
+        filter: &TagFilter,
+    ) -> Vec<(String, f32, f32, f32)> {
         let mut s = self
             .data
             .iter()
-            .filter(|(_k, v)| v.is_active_on(d))
+            .filter(|(_k, v)| v.is_active_on_with(d, end_inclusive) && filter.matches(v))
             .map(|(_k, v)| {
                 (
                     String::from(v.get_name()),
-                    v.get_amount_total().to_f32().unwrap(),
-                    v.per_diem().to_f32().unwrap(),
+                    crate::to_f32_safe(&v.get_amount_total_at(d)),
+                    crate::to_f32_safe(&v.per_period_at(period, d)),
                     v.get_progress(Some(*d)),
                 )
             })
             .collect::<Vec<(String, f32, f32, f32)>>();
         // sort the results descending by completion
         s.sort_by(|a, b| (b.3).partial_cmp(&a.3).unwrap());
+        if let Some(n) = limit {
+            s.truncate(n);
+        }
         s
     }
     /// Return aggregation summary for tags
     ///
+    /// The full per diem of a transaction is added to each of its tags,
+    /// so a transaction with two tags is counted twice and the tags
+    /// total is more than the cost of life, see [`DataStore::tags_split`]
+    ///
     pub fn tags(&self, d: &NaiveDate) -> Vec<(String, usize, f32)> {
+        self.aggregate_tags(d, false)
+    }
+    /// Return aggregation summary for tags, dividing the per diem
+    /// of each transaction equally among its tags
+    ///
+    /// The tags total is the cost of life of the tagged transactions,
+    /// the transactions without tags are not counted
+    ///
+    pub fn tags_split(&self, d: &NaiveDate) -> Vec<(String, usize, f32)> {
+        self.aggregate_tags(d, true)
+    }
+    /// Aggregate the per diem of the active transactions by tag
+    fn aggregate_tags(&self, d: &NaiveDate, split: bool) -> Vec<(String, usize, f32)> {
         // counters here
         let mut agg: HashMap<String, (usize, BigDecimal)> = HashMap::new();
         // aggregate tags
@@ -112,10 +640,19 @@ impl DataStore {
             .iter()
             .filter(|(_h, tx)| tx.is_active_on(d))
             .for_each(|(_h, tx)| {
-                tx.get_tags().iter().for_each(|tg| {
+                let tags = tx.get_tags();
+                // the transactions without tags are not counted
+                if tags.is_empty() {
+                    return;
+                }
+                let per_diem = match split {
+                    true => tx.per_diem_raw_at(d) / BigDecimal::from(tags.len() as i64),
+                    false => tx.per_diem_at(d),
+                };
+                tags.iter().for_each(|tg| {
                     let (n, a) = match agg.get(tg) {
-                        Some((n, a)) => (n + 1, a + tx.per_diem()),
-                        None => (1, tx.per_diem()),
+                        Some((n, a)) => (n + 1, a + &per_diem),
+                        None => (1, per_diem.clone()),
                     };
                     agg.insert(tg.to_string(), (n, a));
                     // * agg.entry(*tg).or_insert((1, tx.per_diem())) +=(1, tx.per_diem());
@@ -124,24 +661,613 @@ impl DataStore {
         // return
         let mut s = agg
             .iter()
-            .map(|(tag, v)| (tag.to_string(), v.0, v.1.clone().to_f32().unwrap()))
+            .map(|(tag, v)| (tag.to_string(), v.0, crate::to_f32_safe(&v.1)))
             .collect::<Vec<(String, usize, f32)>>();
         // sort the results descending by count
         s.sort_by(|a, b| (b.2).partial_cmp(&a.2).unwrap());
         s
     }
+    /// Get the share of an annual income taken by the cost of life,
+    /// in total and by tag
+    ///
+    /// # Arguments
+    ///
+    /// * `on` - The date to compute the cost of life
+    /// * `annual_income` - The income over a year
+    ///
+    /// The shares are fractions of the income (eg. 0.25 for 25%) of the
+    /// annual cost of the active transactions. As for [`DataStore::tags`] the
+    /// full annual cost of a transaction is added to each of its tags.
+    /// The tags are sorted descending by share, with no income the shares are zero
+    pub fn share_of_income(
+        &self,
+        on: &NaiveDate,
+        annual_income: BigDecimal,
+    ) -> (f32, Vec<(String, f32)>) {
+        let mut agg: BTreeMap<String, BigDecimal> = BTreeMap::new();
+        self.data
+            .values()
+            .filter(|tx| tx.is_active_on(on))
+            .for_each(|tx| {
+                tx.get_tags().into_iter().for_each(|tag| {
+                    *agg.entry(tag).or_insert_with(BigDecimal::zero) +=
+                        tx.per_period_at(&Period::Year, on);
+                });
+            });
+        let share = |cost: &BigDecimal| match annual_income > BigDecimal::zero() {
+            true => crate::to_f32_safe(&(cost / &annual_income)),
+            false => 0.0,
+        };
+        let total = share(&crate::cost_of_life_per(
+            self.data.values(),
+            on,
+            &Period::Year,
+        ));
+        let mut tags = agg
+            .iter()
+            .map(|(tag, cost)| (tag.to_string(), share(cost)))
+            .collect::<Vec<(String, f32)>>();
+        tags.sort_by(|a, b| (b.1).partial_cmp(&a.1).unwrap());
+        (total, tags)
+    }
+    /// Return aggregation summary for tags over a date range,
+    /// returning a tuple with (tag, count, accrued cost)
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The first date of the range
+    /// * `to` - The last date of the range (included)
+    ///
+    /// A transaction is counted if it is active at least one day in the range
+    /// and it accrues only the cost of the days inside the range
+    /// (see [`TxRecord::cost_over_range`]). The accrued cost of a transaction
+    /// is added in full to each of its tags.
+    ///
+    pub fn tags_over_range(&self, from: &NaiveDate, to: &NaiveDate) -> Vec<(String, usize, f32)> {
+        // counters here
+        let mut agg: HashMap<String, (usize, BigDecimal)> = HashMap::new();
+        // aggregate tags
+        self.data
+            .values()
+            .map(|tx| (tx, tx.cost_over_range(from, to)))
+            .filter(|(tx, _)| tx.get_starts_on() <= *to && *from <= tx.get_ends_on_at(to))
+            .for_each(|(tx, cost)| {
+                tx.get_tags().into_iter().for_each(|tg| {
+                    let e = agg.entry(tg).or_insert((0, BigDecimal::zero()));
+                    e.0 += 1;
+                    e.1 += &cost;
+                });
+            });
+        // return
+        let mut s = agg
+            .iter()
+            .map(|(tag, v)| (tag.to_string(), v.0, crate::to_f32_safe(&v.1)))
+            .collect::<Vec<(String, usize, f32)>>();
+        // sort the results descending by accrued cost
+        s.sort_by(|a, b| (b.2).partial_cmp(&a.2).unwrap());
+        s
+    }
+    /// Group the accrued cost by ISO week, returning a tuple with
+    /// (ISO year, ISO week number, accrued cost)
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The first date of the range
+    /// * `to` - The last date of the range (included)
+    /// * `currency` - The ISO 4217 code of the transactions to sum
+    ///
+    /// The weeks start on Monday, the first and last week are
+    /// clipped to the range. The ISO year can differ from the calendar
+    /// year for the days around new year (eg. 2021-01-01 is in week 53 of 2020).
+    /// The amounts in other currencies are not summed
+    ///
+    pub fn group_by_iso_week(
+        &self,
+        from: &NaiveDate,
+        to: &NaiveDate,
+        currency: &str,
+    ) -> Vec<(i32, u32, BigDecimal)> {
+        let mut weeks = Vec::new();
+        // the monday of the first week
+        let mut monday = *from - Duration::days(from.weekday().num_days_from_monday().into());
+        while monday <= *to {
+            let start = max(monday, *from);
+            let end = min(monday + Duration::days(6), *to);
+            let cost = self
+                .data
+                .values()
+                .filter(|tx| tx.get_currency() == currency)
+                .map(|tx| tx.cost_over_range(&start, &end))
+                .sum::<BigDecimal>();
+            let week = monday.iso_week();
+            weeks.push((week.year(), week.week(), cost));
+            monday += Duration::weeks(1);
+        }
+        weeks
+    }
+    /// Get the cost accrued over the trailing days
+    ///
+    /// # Arguments
+    ///
+    /// * `end` - The last day of the range (included)
+    /// * `days` - The number of days of the range
+    /// * `currency` - The ISO 4217 code of the transactions to sum
+    ///
+    /// The range is the `days` days up to `end`, each transaction accrues
+    /// only the cost of its days inside the range (see [`TxRecord::cost_over_range`]),
+    /// so a one-off expense counts in full on its day. The amounts in other
+    /// currencies are not summed. With no days the cost is zero
+    ///
+    pub fn trailing_cost(&self, end: &NaiveDate, days: i64, currency: &str) -> BigDecimal {
+        if days <= 0 {
+            return BigDecimal::zero();
+        }
+        let from = *end - Duration::days(days - 1);
+        self.data
+            .values()
+            .filter(|tx| tx.get_currency() == currency)
+            .map(|tx| tx.cost_over_range(&from, end))
+            .sum()
+    }
+    /// Get the average daily cost over the trailing days, rounded
+    /// to the decimals of the currency, see [`DataStore::trailing_cost`]
+    ///
+    /// The average evens out the one-off expenses over the range
+    pub fn trailing_average_daily(&self, end: &NaiveDate, days: i64, currency: &str) -> BigDecimal {
+        if days <= 0 {
+            return BigDecimal::zero();
+        }
+        crate::round_half_up(
+            &(self.trailing_cost(end, days, currency) / BigDecimal::from(days)),
+            crate::currency_exponent(currency),
+        )
+    }
+    /// Return aggregation summary for categories, returning a tuple with
+    /// (category, count, cost per day)
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date to filter for active transactions
+    /// * `currency` - The ISO 4217 code of the transactions to sum
+    ///
+    /// the transactions without a category or in other currencies
+    /// are not included, the results are sorted descending by cost
+    pub fn by_category(&self, d: &NaiveDate, currency: &str) -> Vec<(String, usize, f32)> {
+        // counters here
+        let mut agg: HashMap<&str, (usize, BigDecimal)> = HashMap::new();
+        self.data
+            .values()
+            .filter(|tx| tx.is_active_on(d) && tx.get_currency() == currency)
+            .for_each(|tx| {
+                if let Some(c) = tx.get_category() {
+                    let e = agg.entry(c).or_insert((0, BigDecimal::zero()));
+                    e.0 += 1;
+                    e.1 += tx.per_diem_at(d);
+                }
+            });
+        // return
+        let mut s = agg
+            .iter()
+            .map(|(c, v)| (c.to_string(), v.0, crate::to_f32_safe(&v.1)))
+            .collect::<Vec<(String, usize, f32)>>();
+        s.sort_by(|a, b| (b.2).partial_cmp(&a.2).unwrap());
+        s
+    }
+    /// Return the cost of life by tag for a date
+    ///
+    /// a transaction with many tags is counted for each tag,
+    /// so the sum of the costs can exceed the cost of life
+    pub fn cost_by_tag(&self, d: &NaiveDate) -> BTreeMap<String, f32> {
+        self.tags(d)
+            .into_iter()
+            .map(|(tag, _count, cost)| (tag, cost))
+            .collect()
+    }
+    /// Compute the cost of life of a tag over a date range
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag to compute the cost for
+    /// * `from` - The first date of the series
+    /// * `to` - The last date of the series (included)
+    /// * `step` - The interval between two dates of the series
+    ///
+    /// Returns a (date, cost of life) tuple for each step, a tag
+    /// that is not found produces a series of zeros.
+    /// If the step is empty (zero days) the series is empty
+    ///
+    pub fn tag_timeseries(
+        &self,
+        tag: &str,
+        from: &NaiveDate,
+        to: &NaiveDate,
+        step: Lifetime,
+    ) -> Vec<(NaiveDate, f32)> {
+        let mut series = Vec::new();
+        let mut d = *from;
+        while d <= *to {
+            let txs = self.data.values().filter(|tx| tx.has_tag(tag));
+            series.push((d, crate::to_f32_safe(&crate::cost_of_life(txs, &d))));
+            // move to the next step
+            let days = step.get_days_since(&d);
+            if days <= 0 {
+                return Vec::new();
+            }
+            d += Duration::days(days);
+        }
+        series
+    }
+    /// Return the records active on a date that expire within a number of days
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The date to start looking for expiring records
+    /// * `days` - The number of days after `from` to include
+    ///
+    /// the records are sorted by end date, the ones expiring first on top.
+    /// The records that repeat until cancelled never expire
+    ///
+    pub fn expiring_within(&self, from: &NaiveDate, days: i64) -> Vec<&TxRecord> {
+        let until = *from + Duration::days(days);
+        let mut s = self
+            .data
+            .values()
+            .filter(|tx| !tx.is_indefinite())
+            .filter(|tx| tx.is_active_on(from) && tx.get_ends_on() <= until)
+            .collect::<Vec<&TxRecord>>();
+        s.sort_by_key(|tx| tx.get_ends_on());
+        s
+    }
     /// Insert a new tx record
     /// if the record exists returns the existing one
     ///
     /// TODO: handle duplicates more gracefully
     pub fn insert(&mut self, tx: &TxRecord) -> Option<TxRecord> {
+        let (th, old) = self.put(tx);
+        self.audit(AuditOp::Insert, th);
+        old
+    }
+    /// Remove a tx record
+    /// returns the removed record, if it exists
+    ///
+    pub fn remove(&mut self, hash: &blake3::Hash) -> Option<TxRecord> {
+        let old = self.take(hash);
+        if old.is_some() {
+            self.audit(AuditOp::Remove, *hash);
+        }
+        old
+    }
+    /// Replace an existing tx record with a new one
+    /// returns the replaced record, if it exists
+    ///
+    /// If there is no record for the hash the datastore is left untouched
+    /// and nothing is audited
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the record to replace, as returned by [`DataStore::list`]
+    /// * `tx` - The new version of the record
+    ///
+    pub fn update(&mut self, hash: &blake3::Hash, tx: &TxRecord) -> Option<TxRecord> {
+        let old = self.take(hash)?;
+        let (th, _) = self.put(tx);
+        self.audit(AuditOp::Update { replaced: *hash }, th);
+        Some(old)
+    }
+    /// Replace the tags of an existing tx record
+    /// returns the previous version of the record, if it exists
+    ///
+    /// The tags are not part of the hash, so the record keeps its hash,
+    /// the change is audited as an update
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the record, as returned by [`DataStore::list`]
+    /// * `tags` - The new tags of the record
+    ///
+    pub fn set_tags(&mut self, hash: &blake3::Hash, tags: Vec<&str>) -> Option<TxRecord> {
+        let tx = self.data.get(hash)?.clone_with_tags(tags);
+        self.update(hash, &tx)
+    }
+    /// Merge the records of the same recurring expenses
+    ///
+    /// The records with the same name are sorted by start date and each
+    /// one is merged with the following ones as long as they can be merged,
+    /// following the rules of [`TxRecord::merge_same`] (eg. the same
+    /// subscription added twice for overlapping periods). The merged
+    /// records are removed and the first one is replaced by the merged
+    /// record, the changes are audited.
+    ///
+    /// Returns, for each merge, the records that were merged and the merged record
+    pub fn merge_same(&mut self) -> Vec<(Vec<TxRecord>, TxRecord)> {
+        let mut records = self
+            .data
+            .iter()
+            .map(|(h, tx)| (*h, tx.clone()))
+            .collect::<Vec<(blake3::Hash, TxRecord)>>();
+        records.sort_by_key(|(_, tx)| {
+            (
+                tx.get_name().to_string(),
+                tx.get_starts_on(),
+                *tx.get_recorded_at(),
+            )
+        });
+        // the runs of records that merge together, with the merged record
+        let mut runs: Vec<(Vec<(blake3::Hash, TxRecord)>, TxRecord)> = Vec::new();
+        for (h, tx) in records {
+            let next = runs.last().and_then(|(_, merged)| merged.merge_same(&tx));
+            match (next, runs.last_mut()) {
+                (Some(m), Some((run, merged))) => {
+                    run.push((h, tx));
+                    *merged = m;
+                }
+                _ => runs.push((vec![(h, tx.clone())], tx)),
+            }
+        }
+        runs.into_iter()
+            .filter(|(run, _)| run.len() > 1)
+            .map(|(run, merged)| {
+                // the merged record may have the hash of one of the others
+                run[1..].iter().for_each(|(h, _)| {
+                    self.remove(h);
+                });
+                self.update(&run[0].0, &merged);
+                (run.into_iter().map(|(_, tx)| tx).collect(), merged)
+            })
+            .collect()
+    }
+    /// Rename an existing tx record
+    /// returns the hash of the renamed record
+    ///
+    /// Since the name is part of the hash the record is stored
+    /// under a new key, the change is audited as an update.
+    /// Returns an error if the record does not exist or the name is empty
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The hash of the record to rename, as returned by [`DataStore::list`]
+    /// * `new_name` - The new name of the record
+    ///
+    pub fn rename(
+        &mut self,
+        hash: &blake3::Hash,
+        new_name: &str,
+    ) -> Result<blake3::Hash, CostOfLifeError> {
+        let tx = self
+            .data
+            .get(hash)
+            .ok_or_else(|| {
+                CostOfLifeError::GenericError(format!("transaction not found: {}", hash.to_hex()))
+            })?
+            .with_name(new_name)?;
+        self.update(hash, &tx);
+        Ok(Self::hash(&tx))
+    }
+    /// Get the changes made to the datastore since it was created,
+    /// in chronological order
+    ///
+    /// Loading the records from file is not recorded
+    pub fn audit_log(&self) -> &[AuditEntry] {
+        &self.audit
+    }
+    /// Append the audit log to a file, one entry per line
+    ///
+    pub fn save_audit_log(&self, log_file: &Path) -> Result<(), std::io::Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)?;
+        let mut file = LineWriter::new(file);
+        for entry in &self.audit {
+            writeln!(file, "{}", entry)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+    /// Insert a record and index it, without auditing
+    fn put(&mut self, tx: &TxRecord) -> (blake3::Hash, Option<TxRecord>) {
         let th = Self::hash(tx);
-        // index for search the title and the tags
-        self.index.insert(
-            th,
-            &format!("{} {}", tx.get_name(), tx.get_tags().join(" ")),
+        // index for search the title, the tags and the metadata
+        self.index.insert(th, &Self::index_text(tx));
+        self.invalidate();
+        (th, self.data.insert(th, tx.clone()))
+    }
+    /// Remove a record from the data and the index, without auditing
+    fn take(&mut self, hash: &blake3::Hash) -> Option<TxRecord> {
+        self.index.delete(hash);
+        self.invalidate();
+        self.data.remove(hash)
+    }
+    /// Record a change in the audit log
+    fn audit(&mut self, op: AuditOp, hash: blake3::Hash) {
+        self.audit.push(AuditEntry {
+            op,
+            hash,
+            at: crate::now_local(),
+        });
+    }
+    /// Take a snapshot of the datastore
+    ///
+    /// The snapshot owns a copy of the records, sorted by start date and name
+    pub fn snapshot(&self) -> LedgerSnapshot {
+        let records = self
+            .list()
+            .into_iter()
+            .map(|(_, tx)| tx.clone())
+            .collect::<Vec<TxRecord>>();
+        LedgerSnapshot {
+            generated_at: crate::now_local(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            count: records.len(),
+            records,
+        }
+    }
+    /// Initialize a datastore with the records of a snapshot
+    ///
+    pub fn from_snapshot(snapshot: &LedgerSnapshot) -> DataStore {
+        let mut ds = DataStore::new();
+        snapshot.records.iter().for_each(|tx| {
+            ds.put(tx);
+        });
+        ds
+    }
+    /// Export the datastore to json
+    ///
+    /// The json is a pretty printed snapshot of the datastore
+    pub fn to_json(&self) -> Result<String, CostOfLifeError> {
+        serde_json::to_string_pretty(&self.snapshot())
+            .map_err(|e| CostOfLifeError::GenericError(e.to_string()))
+    }
+    /// Export the datastore to toml
+    ///
+    /// The toml is a snapshot of the datastore,
+    /// the records are exported as an array of tables
+    pub fn to_toml(&self) -> Result<String, CostOfLifeError> {
+        // toml has no null and no struct variants (used by the lifetime),
+        // going through a json value takes care of both
+        let json = serde_json::to_value(self.snapshot())
+            .map_err(|e| CostOfLifeError::GenericError(e.to_string()))?;
+        toml::Value::try_from(strip_nulls(json))
+            .and_then(|v| toml::to_string(&v))
+            .map_err(|e| CostOfLifeError::GenericError(e.to_string()))
+    }
+    /// List all the tx records with their hash
+    ///
+    /// The records are sorted by start date and name
+    pub fn list(&self) -> Vec<(blake3::Hash, &TxRecord)> {
+        let mut txs = self
+            .data
+            .iter()
+            .map(|(h, tx)| (*h, tx))
+            .collect::<Vec<(blake3::Hash, &TxRecord)>>();
+        txs.sort_by(|(_, a), (_, b)| {
+            (a.get_starts_on(), a.get_name()).cmp(&(b.get_starts_on(), b.get_name()))
+        });
+        txs
+    }
+    /// Get all the tx records, active, expired and future ones
+    ///
+    /// The records are sorted by start date and name, see [`DataStore::list`]
+    pub fn all(&self) -> Vec<&TxRecord> {
+        self.list().into_iter().map(|(_, tx)| tx).collect()
+    }
+    /// Parse a tx record from a spec string and insert it
+    ///
+    /// Returns an error if the spec cannot be parsed, otherwise
+    /// the same result of [`DataStore::insert`]
+    ///
+    pub fn insert_str(&mut self, spec: &str) -> Result<Option<TxRecord>, CostOfLifeError> {
+        self.insert_str_on(spec, &crate::today())
+    }
+    /// Parse a tx record from a spec string as if today was
+    /// a different date and insert it, see [`TxRecord::from_str_on`]
+    ///
+    pub fn insert_str_on(
+        &mut self,
+        spec: &str,
+        on: &NaiveDate,
+    ) -> Result<Option<TxRecord>, CostOfLifeError> {
+        self.insert_str_in(spec, on, crate::DEFAULT_CURRENCY)
+    }
+    /// Parse a tx record from a spec string as if today was a different
+    /// date, with a different default currency, and insert it,
+    /// see [`TxRecord::from_str_in`]
+    ///
+    pub fn insert_str_in(
+        &mut self,
+        spec: &str,
+        on: &NaiveDate,
+        currency: &str,
+    ) -> Result<Option<TxRecord>, CostOfLifeError> {
+        let tx = self.parse_str_in(spec, on, currency)?;
+        Ok(self.insert(&tx))
+    }
+    /// Parse a tx record from a spec string as [`DataStore::insert_str_in`]
+    /// does, without inserting it
+    ///
+    /// The amounts expressed as a percentage of another transaction
+    /// (eg. `15% of "Dinner"`) are resolved with the records of the datastore
+    pub fn parse_str_in(
+        &self,
+        spec: &str,
+        on: &NaiveDate,
+        currency: &str,
+    ) -> Result<TxRecord, CostOfLifeError> {
+        let spec = self.resolve_percentage(spec)?;
+        TxRecord::from_str_in(&spec, on, currency)
+    }
+    /// Replace an amount expressed as a percentage of another
+    /// transaction (eg. `15% of "Dinner"`) with the concrete amount
+    ///
+    /// The referenced transaction is matched by its exact name, if there
+    /// are many the one that starts last is used. The amount is rounded
+    /// to the decimals of the referenced transaction currency.
+    /// Returns an error if the referenced transaction does not exist
+    fn resolve_percentage(&self, spec: &str) -> Result<String, CostOfLifeError> {
+        let c = match RE_PERCENTAGE.captures(spec) {
+            Some(c) => c,
+            None => return Ok(spec.to_string()),
+        };
+        let name = &c[2];
+        let reference = self.find_by_name(name).pop().ok_or_else(|| {
+            CostOfLifeError::InvalidAmount(format!("transaction not found: {}", name))
+        })?;
+        let percentage = crate::parse_amount(&c[1])
+            .ok_or_else(|| CostOfLifeError::InvalidAmount(c[1].to_string()))?;
+        let amount = crate::round_half_up(
+            &(reference.get_amount() * percentage / BigDecimal::from(100)),
+            crate::currency_exponent(reference.get_currency()),
         );
-        self.data.insert(th, tx.clone())
+        Ok(RE_PERCENTAGE
+            .replace(
+                spec,
+                format!(
+                    "{}{}",
+                    amount,
+                    crate::currency_symbol(reference.get_currency())
+                )
+                .as_str(),
+            )
+            .to_string())
+    }
+    /// Parse and insert a list of spec strings, all or nothing
+    ///
+    /// If any of the specs cannot be parsed nothing is inserted
+    /// and the error lists every failure with its position in the list,
+    /// otherwise returns the number of specs inserted
+    ///
+    pub fn load_specs(&mut self, specs: &[&str]) -> Result<usize, CostOfLifeError> {
+        let mut txs = Vec::new();
+        let mut errors = Vec::new();
+        for (i, spec) in specs.iter().enumerate() {
+            match TxRecord::from_str(spec) {
+                Ok(tx) => txs.push(tx),
+                Err(e) => errors.push(format!("#{} \"{}\": {}", i + 1, spec, e)),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(CostOfLifeError::GenericError(format!(
+                "{} of {} specs are invalid: {}",
+                errors.len(),
+                specs.len(),
+                errors.join("; ")
+            )));
+        }
+        txs.iter().for_each(|tx| {
+            self.insert(tx);
+        });
+        Ok(txs.len())
+    }
+    /// Parse and insert a JSON array of spec strings, all or nothing
+    ///
+    /// The input looks like `["Rent 1200€ 1m12x #home", "Coffee 2€"]`,
+    /// see [`DataStore::load_specs`] for how the failures are reported.
+    /// Returns the number of specs inserted
+    ///
+    pub fn import_json_specs(&mut self, json: &str) -> Result<usize, CostOfLifeError> {
+        let specs: Vec<String> = serde_json::from_str(json)
+            .map_err(|e| CostOfLifeError::GenericError(format!("invalid json spec list: {}", e)))?;
+        self.load_specs(&specs.iter().map(String::as_str).collect::<Vec<&str>>())
     }
     /// Get the size of the datastore
     ///
@@ -153,10 +1279,16 @@ impl DataStore {
     ///
     pub fn size(&self, on: Option<NaiveDate>) -> usize {
         match on {
-            Some(date) => self.summary(&date).len(),
+            Some(date) => self.summary(&date, None).len(),
             None => self.data.len(),
         }
     }
+    /// Get the latest time a record was recorded at,
+    /// None if the datastore is empty
+    ///
+    pub fn last_modified(&self) -> Option<DateTime<FixedOffset>> {
+        self.data.values().map(|tx| *tx.get_recorded_at()).max()
+    }
     // The output is wrapped in a Result to allow matching on errors
     // Returns an Iterator to the Reader of the lines of the file.
     fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
@@ -166,29 +1298,56 @@ impl DataStore {
         let file = File::open(filename)?;
         Ok(io::BufReader::new(file).lines())
     }
+    /// Build the text used to index a TxRecord for search
+    ///
+    /// The text contains the name, the category, the tags and the metadata values
+    fn index_text(tx: &TxRecord) -> String {
+        let meta = tx
+            .get_metadata()
+            .into_iter()
+            .map(|(_k, v)| v)
+            .collect::<Vec<String>>();
+        format!(
+            "{} {} {} {} {}",
+            tx.get_name(),
+            tx.get_category().unwrap_or_default(),
+            tx.get_tags().join(" "),
+            meta.join(" "),
+            tx.get_note().unwrap_or_default()
+        )
+    }
     /// Compute the blake3 has for a TxRecord
     ///
     /// The hash is calculated on
     /// - name
-    /// - lifetime
+    /// - lifetime (normalized)
     /// - starts_on
     /// - amount
+    /// - currency
     ///
     fn hash(tx: &TxRecord) -> blake3::Hash {
         let fields = format!(
-            "{}:{}:{}:{}",
+            "{}:{}:{}:{}:{}",
             tx.get_name(),
             tx.get_amount(),
-            tx.get_lifetime(),
+            tx.get_currency(),
+            tx.get_lifetime().normalize(),
             tx.get_starts_on(),
         );
         blake3::hash(fields.as_bytes())
     }
 }
+/// Quote a csv field if it contains separators, quotes or new lines
+fn csv_field(value: &str) -> String {
+    match value.contains(&[',', '"', '\n'][..]) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ::costoflife::{self, TxRecord};
     #[test]
     fn test_datastore() {
         let mut ds = DataStore::new();
@@ -196,37 +1355,889 @@ mod tests {
         ds.insert(&TxRecord::new("Test#1", "10").unwrap());
         ds.insert(&TxRecord::new("Test#2", "10").unwrap());
         // simple insert
-        assert_eq!(ds.cost_of_life(&costoflife::today()), 20.0);
+        assert_eq!(ds.cost_of_life(&crate::today()), 20.0);
         // summary test
-        let summary = ds.summary(&costoflife::today());
+        let summary = ds.summary(&crate::today(), None);
+        assert_eq!(summary.len(), 2);
+        let summary = ds.summary(&crate::today(), Some(1));
+        assert_eq!(summary.len(), 1);
+        let summary = ds.summary(&crate::today(), Some(10));
+        assert_eq!(summary.len(), 2);
+    }
+
+    #[test]
+    fn test_summary_per() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::new("Test#1", "10").unwrap());
+        ds.insert(&TxRecord::new("Test#2", "10").unwrap());
+        let summary = ds.summary_per(&crate::today(), None, &Period::Week);
         assert_eq!(summary.len(), 2);
-        // test tags
+        assert_eq!(summary[0].2, 70.0);
+        let summary = ds.summary_per(&crate::today(), Some(1), &Period::Year);
+        assert_eq!(summary.len(), 1);
+        assert_eq!(summary[0].2, 3652.5);
+        assert_eq!(ds.cost_of_life_per(&crate::today(), &Period::Week), 140.0);
+    }
+
+    #[test]
+    fn test_tags() {
         let mut ds = DataStore::new();
         // insert one entry
         ds.insert(&TxRecord::from_str("Test#1 10€ #tag1").unwrap());
         ds.insert(&TxRecord::from_str("Test#2 20€ #tag2").unwrap());
         ds.insert(&TxRecord::from_str("Test#3 50€ #tag3").unwrap());
         ds.insert(&TxRecord::from_str("Test#4 40€ #tag2").unwrap());
-        let tags = ds.tags(&costoflife::today());
+        let tags = ds.tags(&crate::today());
         assert_eq!(tags.len(), 3);
         // tag2
         let got = &tags[0];
-        let exp = (String::from("tag2"), 2 as usize, 60.0);
+        let exp = (String::from("tag2"), 2, 60.0);
         assert_eq!(*got, exp);
         // tag3
         let got = &tags[1];
-        let exp = (String::from("tag3"), 1 as usize, 50.0);
+        let exp = (String::from("tag3"), 1, 50.0);
         assert_eq!(*got, exp);
-        // test search
+    }
+
+    #[test]
+    fn test_tags_split() {
+        let mut sds = DataStore::new();
+        sds.insert_str("Rent 900€ 1m 010121 #home #fixed").unwrap();
+        sds.insert_str("Gym 31€ 1m 010121 #sport #health #fixed")
+            .unwrap();
+        sds.insert_str("Coffee 2€ 150121 #food").unwrap();
+        sds.insert_str("Phone 31€ 1m 010121").unwrap();
+        let d = crate::date(15, 1, 2021);
+        let full: f32 = sds.tags(&d).iter().map(|(_, _, c)| c).sum();
+        assert!(full > sds.cost_of_life(&d));
+        let split = sds.tags_split(&d);
+        assert_eq!(split.len(), 5);
+        assert_eq!((split[0].0.as_str(), split[0].1), ("fixed", 2));
+        assert!((split[0].2 - 14.85).abs() < 0.01);
+        assert_eq!((split[1].0.as_str(), split[1].1), ("home", 1));
+        assert!((split[1].2 - 14.52).abs() < 0.01);
+        assert_eq!(split[2], (String::from("food"), 1, 2.0));
+        let total: f32 = split.iter().map(|(_, _, c)| c).sum();
+        // the untagged phone is not counted
+        let tagged = sds.cost_of_life(&d) - 1.0;
+        assert!((total - tagged).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Test#1 10€ #tag1").unwrap());
+        ds.insert(&TxRecord::from_str("Test#2 20€ #tag2").unwrap());
+        ds.insert(&TxRecord::from_str("Test#3 50€ #tag3").unwrap());
+        ds.insert(&TxRecord::from_str("Test#4 40€ #tag2").unwrap());
         assert_eq!(ds.search("tag").len(), 4);
         assert_eq!(ds.search("whatever").len(), 0);
-        // test load
+        // search on metadata
+        ds.insert(&TxRecord::from_str("Book 12€ vendor=Amazon").unwrap());
+        assert_eq!(ds.search("amazon").len(), 1);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 1000€ 1m 010121 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Sushi, ramen 3000¥ 020121 #food #eat").unwrap());
+        let csv = ds.to_csv();
+        let lines = csv.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "Rent,1000.00,EUR,1m1x,2021-01-01,2021-01-31,32.26,home"
+        );
+        assert_eq!(
+            lines[2],
+            "\"Sushi, ramen\",3000,JPY,1d1x,2021-01-02,2021-01-02,3000,eat food"
+        );
+        assert!(!csv.contains('€'));
+        assert!(!csv.contains('¥'));
+    }
+
+    #[test]
+    fn test_tag_timeseries() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 310€ 1m 010121 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Power 28€ 1m 010221 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Phone 31€ 1m 010121 #tel").unwrap());
+        let from = crate::date(15, 1, 2021);
+        let to = crate::date(15, 3, 2021);
+        let step = Lifetime::Month {
+            amount: 1,
+            times: 1,
+        };
+        let got = ds.tag_timeseries("home", &from, &to, step);
+        assert_eq!(
+            got,
+            vec![
+                (crate::date(15, 1, 2021), 10.0),
+                (crate::date(15, 2, 2021), 1.0),
+                (crate::date(15, 3, 2021), 0.0),
+            ]
+        );
+        let got = ds.tag_timeseries("whatever", &from, &to, step);
+        assert_eq!(got.len(), 3);
+        assert!(got.iter().all(|(_, v)| *v == 0.0));
+    }
+
+    #[test]
+    fn test_cost_by_tag() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 310€ 1m 010121 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Power 28€ 1m 010221 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Phone 31€ 1m 010121 #tel").unwrap());
+        let got = ds.cost_by_tag(&crate::date(15, 1, 2021));
+        assert_eq!(got.len(), 2);
+        assert_eq!(got.get("home"), Some(&10.0));
+        assert_eq!(got.get("tel"), Some(&1.0));
+        assert!(DataStore::new().cost_by_tag(&crate::today()).is_empty());
+    }
+
+    #[test]
+    fn test_by_category() {
+        let mut cds = DataStore::new();
+        cds.insert_str("Rent 310€ 1m 010121 ::Housing").unwrap();
+        cds.insert_str("Power 31€ 1m 010121 ::Housing").unwrap();
+        cds.insert_str("Bus 62€ 1m 010121 ::Transport").unwrap();
+        cds.insert_str("Coffee 2€ 150121").unwrap();
+        cds.insert_str("Taxi 20$ 150121 ::Transport").unwrap();
+        let got = cds.by_category(&crate::date(15, 1, 2021), "EUR");
+        assert_eq!(
+            got,
+            vec![
+                (String::from("Housing"), 2, 11.0),
+                (String::from("Transport"), 1, 2.0),
+            ]
+        );
+        let got = cds.by_category(&crate::date(15, 1, 2021), "USD");
+        assert_eq!(got, vec![(String::from("Transport"), 1, 20.0)]);
+        assert!(cds.by_category(&crate::date(15, 2, 2021), "EUR").is_empty());
+    }
+
+    #[test]
+    fn test_tags_over_range() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 310€ 1m 010121 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Power 28€ 1m 010221 #home").unwrap());
+        ds.insert(&TxRecord::from_str("Phone 31€ 1m 010121 #tel").unwrap());
+        let got = ds.tags_over_range(&crate::date(20, 1, 2021), &crate::date(5, 2, 2021));
+        assert_eq!(
+            got,
+            vec![
+                (String::from("home"), 2, 125.0),
+                (String::from("tel"), 1, 12.0),
+            ]
+        );
+        let got = ds.tags_over_range(&crate::date(1, 1, 2022), &crate::date(5, 2, 2022));
+        assert!(got.is_empty());
+    }
+
+    #[test]
+    fn test_group_by_iso_week() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 310€ 1m 010121").unwrap());
+        ds.insert(&TxRecord::from_str("Coffee 2€ 301220").unwrap());
+        ds.insert(&TxRecord::from_str("Tea 3$ 301220").unwrap());
+        let (from, to) = (crate::date(29, 12, 2020), crate::date(5, 1, 2021));
+        let got = ds.group_by_iso_week(&from, &to, "EUR");
+        assert_eq!(
+            got,
+            vec![
+                (2020, 53, crate::parse_amount("32").unwrap()),
+                (2021, 1, crate::parse_amount("20").unwrap()),
+            ]
+        );
+        let got = ds.group_by_iso_week(&from, &to, "USD");
+        assert_eq!(
+            got,
+            vec![
+                (2020, 53, crate::parse_amount("3").unwrap()),
+                (2021, 1, BigDecimal::zero()),
+            ]
+        );
+        let got = ds.group_by_iso_week(&crate::date(4, 1, 2021), &crate::date(3, 1, 2021), "EUR");
+        assert!(got.is_empty());
+    }
+
+    #[test]
+    fn test_insert_str() {
+        // insert from spec string
+        let mut ds = DataStore::new();
+        assert!(ds.insert_str("Rent 1000€ 1m 010121").unwrap().is_none());
+        assert!(ds.insert_str("Rent 1000€ 1m 010121").unwrap().is_some());
+        assert!(ds.insert_str("Rent 1m 010121").is_err());
+        assert_eq!(ds.size(None), 1);
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1000€ 1m 010221").unwrap();
+        ds.insert_str("Rent 900€ 1m 010121").unwrap();
+        ds.insert_str("Rent deposit 2000€ 010121").unwrap();
+        let got = ds.find_by_name("Rent");
+        assert_eq!(got.len(), 2);
+        assert_eq!(got[0].get_starts_on(), crate::date(1, 1, 2021));
+        assert_eq!(got[1].get_starts_on(), crate::date(1, 2, 2021));
+        assert!(ds.find_by_name("rent").is_empty());
+        assert!(ds.find_by_name("Ren").is_empty());
+        assert_eq!(ds.find_by_name("Rent deposit").len(), 1);
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1000€ 1m 010121").unwrap();
+        ds.insert_str("Phone 30€ 1m 010121").unwrap();
+        let (h, _) = ds.list()[1];
+        ds.update(&h, &TxRecord::from_str("Rent 1100€ 1m 010121").unwrap());
+        let (h2, _) = ds.list()[1];
+        ds.remove(&h2);
+        ds.remove(&h2);
+        let log = ds.audit_log();
+        assert_eq!(log.len(), 4);
+        assert_eq!(log[0].op, AuditOp::Insert);
+        assert_eq!(log[0].hash, h);
+        assert_eq!(log[1].op, AuditOp::Insert);
+        assert_eq!(log[2].op, AuditOp::Update { replaced: h });
+        assert_eq!(log[2].hash, h2);
+        assert_eq!(log[3].op, AuditOp::Remove);
+        assert_eq!(log[3].hash, h2);
+        assert!(log.windows(2).all(|w| w[0].at <= w[1].at));
+        assert!(log[2]
+            .to_string()
+            .ends_with(&format!("::update::{}::{}", h2, h)));
+        // loading is not audited
+        let mut ds = DataStore::new();
+        ds.load(Path::new("./testdata/costoflife.data.txt"))
+            .unwrap();
+        assert!(ds.audit_log().is_empty());
+    }
+
+    #[test]
+    fn test_load_str() {
+        let mut ds = DataStore::new();
+        ds.load_str(&std::fs::read_to_string("./testdata/costoflife.data.txt").unwrap());
+        assert_eq!(ds.size(None), 5);
+        let mut ds = DataStore::new();
+        ds.load_str("not a record\n\n2021-01-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m");
+        assert_eq!(ds.size(None), 1);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1000€ 1m12x 010121 #home ::Housing vendor=Acme")
+            .unwrap();
+        ds.insert_str("Sushi 3000¥ 020121 #\"eat out\"").unwrap();
+        ds.insert_str("Spotify 30€ 1m /3 010121").unwrap();
+        let snapshot = ds.snapshot();
+        assert_eq!(snapshot.count, 3);
+        assert_eq!(snapshot.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(snapshot.records[0].get_name(), "Rent");
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let got: LedgerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(got.count, snapshot.count);
+        assert_eq!(got.generated_at, snapshot.generated_at);
+        assert_eq!(got.records, snapshot.records);
+        let got = DataStore::from_snapshot(&got);
+        assert_eq!(got.size(None), 3);
+        assert!(got.audit_log().is_empty());
+        assert_eq!(
+            got.cost_of_life(&crate::date(2, 1, 2021)),
+            ds.cost_of_life(&crate::date(2, 1, 2021))
+        );
+        assert_eq!(got.to_csv(), ds.to_csv());
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let mut gds = DataStore::new();
+        gds.insert_str("Rent 1000€ 1m6x 010121").unwrap();
+        gds.insert_str("Rent 1000€ 1m5x 010821").unwrap();
+        gds.insert_str("Rent 1100€ 1m 011221").unwrap();
+        gds.insert_str("Rent 1100€ 1m 100122").unwrap();
+        gds.insert_str("Rent 1100€ 1m 150122").unwrap();
+        gds.insert_str("Gym 30€ 1m 010121").unwrap();
+        assert_eq!(
+            gds.find_gaps("Rent"),
+            vec![
+                (crate::date(1, 7, 2021), crate::date(31, 7, 2021)),
+                (crate::date(1, 1, 2022), crate::date(9, 1, 2022)),
+            ]
+        );
+        assert!(gds.find_gaps("Gym").is_empty());
+        assert!(gds.find_gaps("rent").is_empty());
+        // a record until cancelled covers the following ones
+        let mut gds = DataStore::new();
+        gds.insert_str("Rent 1000€ 1m+ 010121").unwrap();
+        gds.insert_str("Rent 1100€ 1m 010621").unwrap();
+        assert!(gds.find_gaps("Rent").is_empty());
+    }
+
+    #[test]
+    fn test_load_many() {
+        let mut lds = DataStore::new();
+        lds.load_many(&[
+            Path::new("./testdata/costoflife.2022.data.txt"),
+            Path::new("./testdata/costoflife.2023.data.txt"),
+        ])
+        .unwrap();
+        assert_eq!(lds.size(None), 4);
+        assert_eq!(lds.find_by_name("Gym").len(), 1);
+        assert_eq!(lds.find_by_name("Rent").len(), 2);
+        assert_eq!(lds.size(Some(crate::date(1, 6, 2022))), 2);
+        assert_eq!(lds.size(Some(crate::date(1, 6, 2023))), 2);
+        assert!(lds.find_gaps("Rent").is_empty());
+    }
+
+    #[test]
+    fn test_trailing_cost() {
+        let mut wds = DataStore::new();
+        wds.insert_str("Rent 310€ 1m 010121").unwrap();
+        wds.insert_str("Laptop 1500€ 150121").unwrap();
+        wds.insert_str("Ramen 1000¥ 150121").unwrap();
+        let end = crate::date(31, 1, 2021);
+        assert_eq!(
+            wds.trailing_cost(&end, 31, "EUR"),
+            BigDecimal::from_str("1810").unwrap()
+        );
+        assert_eq!(
+            wds.trailing_average_daily(&end, 31, "EUR"),
+            BigDecimal::from_str("58.39").unwrap()
+        );
+        assert_eq!(
+            wds.trailing_cost(&end, 10, "EUR"),
+            BigDecimal::from_str("100").unwrap()
+        );
+        assert_eq!(
+            wds.trailing_average_daily(&end, 10, "EUR"),
+            BigDecimal::from_str("10.00").unwrap()
+        );
+        assert_eq!(wds.trailing_cost(&end, 0, "EUR"), BigDecimal::zero());
+        assert_eq!(
+            wds.trailing_average_daily(&end, -1, "EUR"),
+            BigDecimal::zero()
+        );
+        assert_eq!(
+            wds.trailing_cost(&crate::date(15, 1, 2021), 1, "EUR"),
+            BigDecimal::from_str("1510").unwrap()
+        );
+        // the yen have no decimals
+        assert_eq!(
+            wds.trailing_cost(&end, 31, "JPY"),
+            BigDecimal::from_str("1000").unwrap()
+        );
+        assert_eq!(
+            wds.trailing_average_daily(&end, 31, "JPY"),
+            BigDecimal::from_str("32").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_indefinite() {
+        // records that repeat until cancelled
+        let mut eds = DataStore::new();
+        eds.load_str(
+            "2020-01-01T00:00:00+00:00::2020-01-01::Rent 310€ 1m+\n\
+             2020-01-01T00:00:00+00:00::2020-01-01::Phone 31€ 1m12x",
+        );
+        assert_eq!(eds.size(None), 2);
+        let d = crate::date(15, 6, 2022);
+        assert_eq!(eds.summary(&d, None).len(), 1);
+        assert_eq!(eds.cost_of_life(&d), 10.19);
+        assert!(eds.expiring_within(&d, 3650).is_empty());
+        assert_eq!(eds.expiring_within(&crate::date(1, 1, 2020), 3650).len(), 1);
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let mut fds = DataStore::new();
+        fds.insert_str("Netflix 31€ 1m 010121 #subscriptions #movies")
+            .unwrap();
+        fds.insert_str("Spotify 62€ 1m 010121 #Subscriptions")
+            .unwrap();
+        fds.insert_str("Rent 310€ 1m 010121 #home").unwrap();
+        fds.insert_str("Coffee 1€ 150121").unwrap();
+        let d = crate::date(15, 1, 2021);
+        let all = TagFilter::default();
+        assert!(all.is_empty());
+        assert_eq!(
+            fds.cost_of_life_filtered(&d, &Period::Day, &all),
+            fds.cost_of_life(&d)
+        );
+        let subs = TagFilter::new(&["subscriptions"], &[]);
+        assert_eq!(subs.to_string(), "#subscriptions");
+        assert_eq!(fds.cost_of_life_filtered(&d, &Period::Day, &subs), 3.0);
+        assert_eq!(fds.summary_filtered(&d, None, &Period::Day, &subs).len(), 2);
+        let no_movies = TagFilter::new(&["SUBSCRIPTIONS"], &["Movies"]);
+        assert_eq!(no_movies.to_string(), "#subscriptions -#movies");
+        assert_eq!(fds.cost_of_life_filtered(&d, &Period::Day, &no_movies), 2.0);
+        // the records without tags pass only the exclude filters
+        let no_home = TagFilter::new(&[], &["home"]);
+        assert_eq!(fds.cost_of_life_filtered(&d, &Period::Day, &no_home), 4.0);
+        assert_eq!(
+            fds.summary_filtered(&d, None, &Period::Day, &no_home).len(),
+            3
+        );
+        // the filter applies to the records active on the date
+        let d = crate::date(10, 1, 2021);
+        assert_eq!(fds.cost_of_life_filtered(&d, &Period::Day, &no_home), 3.0);
+        let nothing = TagFilter::new(&["travel"], &[]);
+        assert_eq!(fds.cost_of_life_filtered(&d, &Period::Day, &nothing), 0.0);
+    }
+
+    #[test]
+    fn test_merge_same() {
+        // merge the same recurring expenses
+        let mut nds = DataStore::new();
+        nds.insert_str("Netflix 10€ 1m3x 010121 #movies").unwrap();
+        nds.insert_str("Netflix 10€ 1m3x 010321 #tv").unwrap();
+        nds.insert_str("Netflix 10€ 1m 010621").unwrap();
+        nds.insert_str("Netflix 10€ 1m 010921").unwrap();
+        nds.insert_str("Rent 310€ 1m 010121").unwrap();
+        nds.insert_str("Rent 310€ 1m 010121 #home").unwrap();
+        let entries = nds.audit_log().len();
+        let merges = nds.merge_same();
+        assert_eq!(merges.len(), 1);
+        let (merged, tx) = &merges[0];
+        assert_eq!(merged.len(), 3);
+        assert_eq!(tx.get_lifetime().to_string(), "1m6x");
+        assert_eq!(tx.get_tags(), vec!["movies", "tv"]);
+        assert_eq!(nds.size(None), 3);
+        assert_eq!(nds.find_by_name("Netflix").len(), 2);
+        assert_eq!(nds.audit_log().len(), entries + 3);
+        // nothing left to merge
+        assert!(nds.merge_same().is_empty());
+        // the merged record has the hash of one of the merged ones
+        let mut nds = DataStore::new();
+        nds.insert_str("Gym 30€ 1m 010121").unwrap();
+        nds.insert_str("Gym 30€ 1m2x 010121").unwrap();
+        assert_eq!(nds.merge_same().len(), 1);
+        assert_eq!(nds.size(None), 1);
+        assert_eq!(nds.list()[0].1.get_lifetime().to_string(), "1m2x");
+    }
+
+    #[test]
+    fn test_share_of_income() {
+        let mut pds = DataStore::new();
+        pds.insert_str("Rent 1000€ 1y 010121 #home").unwrap();
+        pds.insert_str("Food 10€ 1d 150121 #food #home").unwrap();
+        pds.insert_str("Gym 30€ 1m 010321 #sport").unwrap();
+        let on = crate::date(15, 1, 2021);
+        let (total, tags) = pds.share_of_income(&on, BigDecimal::from(10000));
+        let rent = 1000.0 / 365.0 * 365.25 / 10000.0;
+        let food = 10.0 * 365.25 / 10000.0;
+        assert!((total - (rent + food)).abs() < 0.0001);
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags[0].0, "home");
+        assert!((tags[0].1 - (rent + food)).abs() < 0.0001);
+        assert_eq!(tags[1].0, "food");
+        assert!((tags[1].1 - food).abs() < 0.0001);
+        let (total, tags) = pds.share_of_income(&on, BigDecimal::zero());
+        assert_eq!(total, 0.0);
+        assert!(tags.iter().all(|(_, s)| *s == 0.0));
+    }
+
+    #[test]
+    fn test_export() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1000€ 1m12x 010121 #home ::Housing vendor=Acme")
+            .unwrap();
+        ds.insert_str("Sushi 3000¥ 020121 #\"eat out\"").unwrap();
+        ds.insert_str("Spotify 30€ 1m /3 010121").unwrap();
+        let json = ds.to_json().unwrap();
+        let got: LedgerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(got.records, ds.snapshot().records);
+        let toml = ds.to_toml().unwrap();
+        let got: toml::Value = toml::from_str(&toml).unwrap();
+        assert_eq!(got["count"].as_integer(), Some(3));
+        let records = got["records"].as_array().unwrap();
+        assert_eq!(records[0]["name"].as_str(), Some("Rent"));
+        assert_eq!(records[0]["category"].as_str(), Some("Housing"));
+        assert!(records[1].get("category").is_none());
+    }
+
+    #[test]
+    fn test_percentage() {
+        // percentage of another transaction
+        let mut ds = DataStore::new();
+        ds.insert_str("Dinner 80€ 010121").unwrap();
+        ds.insert_str("Tip 15% of \"Dinner\" 010121 #food").unwrap();
+        let tip = ds.find_by_name("Tip");
+        assert_eq!(tip.len(), 1);
+        assert_eq!(tip[0].get_amount(), crate::parse_amount("12").unwrap());
+        assert_eq!(tip[0].get_tags(), vec!["food"]);
+        let txr = TxRecord::from_string_record(&tip[0].to_string_record()).unwrap();
+        assert_eq!(txr.get_amount(), crate::parse_amount("12").unwrap());
+        // the currency of the referenced transaction is used
+        ds.insert_str("Ramen 1250¥ 020121").unwrap();
+        ds.insert_str("Tip 10.5% of \"Ramen\" 020121").unwrap();
+        let tip = ds.find_by_name("Tip");
+        assert_eq!(tip[1].get_amount(), crate::parse_amount("131").unwrap());
+        assert_eq!(tip[1].get_currency(), "JPY");
+        // the referenced transaction must exist
+        let err = ds.insert_str("Tip 15% of \"Lunch\"").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid amount: transaction not found: Lunch"
+        );
+        assert_eq!(ds.size(None), 4);
+        // parse without inserting
+        let tx = ds
+            .parse_str_in("Tip 10% of \"Dinner\" 1d", &crate::date(1, 1, 2021), "EUR")
+            .unwrap();
+        assert_eq!(tx.get_amount(), crate::parse_amount("8").unwrap());
+        assert_eq!(tx.get_starts_on(), crate::date(1, 1, 2021));
+        assert_eq!(ds.size(None), 4);
+    }
+
+    #[test]
+    fn test_load_specs() {
+        let mut ds = DataStore::new();
+        let specs = vec!["Rent 1000€ 1m 010121", "Phone 30€ 1m 010121"];
+        assert_eq!(ds.load_specs(&specs).unwrap(), 2);
+        assert_eq!(ds.size(None), 2);
+        let specs = vec![
+            "Gym 70€ 1m 010121",
+            "Rent 1m 010121",
+            "Car 2000€ 1001y",
+            "Coffee 2€",
+        ];
+        let err = ds.load_specs(&specs).unwrap_err().to_string();
+        assert!(err.starts_with("2 of 4 specs are invalid"));
+        assert!(err.contains("#2 \"Rent 1m 010121\""));
+        assert!(err.contains("#3 \"Car 2000€ 1001y\""));
+        assert_eq!(ds.size(None), 2);
+        assert_eq!(ds.load_specs(&[]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_hash() {
+        // equivalent lifetimes are the same record
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Gym 70€ 14d 010121").unwrap());
+        ds.insert(&TxRecord::from_str("Gym 70€ 2w 010121").unwrap());
+        assert_eq!(ds.size(None), 1);
+        // the same amount in another currency is another record
+        let mut ds = DataStore::new();
+        ds.insert_str("Netflix 10€ 1m 010121").unwrap();
+        ds.insert_str("Netflix 10$ 1m 010121").unwrap();
+        assert_eq!(ds.size(None), 2);
+    }
+
+    #[test]
+    fn test_expiring() {
+        let mut ds = DataStore::new();
+        ds.insert(&TxRecord::from_str("Rent 1000€ 1m 010121").unwrap());
+        ds.insert(&TxRecord::from_str("Phone 10€ 1w 100121").unwrap());
+        ds.insert(&TxRecord::from_str("Car 9000€ 5y 010121").unwrap());
+        ds.insert(&TxRecord::from_str("Gym 30€ 1m 010221").unwrap());
+        let exp = ds.expiring_within(&crate::date(10, 1, 2021), 30);
+        assert_eq!(exp.len(), 2);
+        assert_eq!(exp[0].get_name(), "Phone");
+        assert_eq!(exp[1].get_name(), "Rent");
+        assert_eq!(ds.expiring_within(&crate::date(10, 1, 2021), 3).len(), 0);
+    }
+
+    #[test]
+    fn test_update_remove() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1000€ 1m 010121 #home").unwrap();
+        ds.insert_str("Phone 30€ 1m 010121").unwrap();
+        let list = ds.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].1.get_name(), "Phone");
+        let (h, _) = list[1];
+        let tx = TxRecord::from_str("Rent 1100€ 1m 010121 #home").unwrap();
+        let old = ds.update(&h, &tx).unwrap();
+        assert_eq!(old.get_amount(), crate::parse_amount("1000").unwrap());
+        assert_eq!(ds.size(None), 2);
+        assert_eq!(ds.cost_of_life(&crate::date(1, 1, 2021)), 36.45);
+        assert_eq!(ds.search("rent").len(), 1);
+        // the old hash is gone, the update is a no-op
+        let audited = ds.audit_log().len();
+        assert!(ds.update(&h, &tx).is_none());
+        let tx = TxRecord::from_str("Gym 40€ 1m 010121").unwrap();
+        assert!(ds.update(&h, &tx).is_none());
+        assert_eq!(ds.size(None), 2);
+        assert_eq!(ds.search("gym").len(), 0);
+        assert_eq!(ds.audit_log().len(), audited);
+        assert_eq!(ds.search("rent").len(), 1);
+        let (h, _) = ds.list()[0];
+        assert!(ds.remove(&h).is_some());
+        assert!(ds.remove(&h).is_none());
+        assert_eq!(ds.size(None), 1);
+        assert_eq!(ds.search("phone").len(), 0);
+    }
+
+    #[test]
+    fn test_import_json_specs() {
+        let mut jds = DataStore::new();
+        let n = jds
+            .import_json_specs(r#"["Rent 1200€ 1m12x #home", "Coffee 2€ 010121"]"#)
+            .unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(jds.size(None), 2);
+        assert_eq!(jds.find_by_name("Rent")[0].get_tags(), vec!["home"]);
+        assert_eq!(jds.import_json_specs("[]").unwrap(), 0);
+        let err = jds
+            .import_json_specs(r#"["Phone 30€", "Nothing", "Cake 0€"]"#)
+            .unwrap_err();
+        assert!(err.to_string().starts_with("2 of 3 specs are invalid"));
+        assert!(err.to_string().contains("#2 \"Nothing\""));
+        assert!(jds.import_json_specs(r#"{"spec": "Phone 30€"}"#).is_err());
+        assert!(jds.import_json_specs(r#"["Phone 30€", 1]"#).is_err());
+        assert!(jds.import_json_specs("not json").is_err());
+        assert_eq!(jds.size(None), 2);
+    }
+
+    #[test]
+    fn test_income_expense_total() {
+        let mut ids = DataStore::new();
+        ids.insert_str("Rent 310€ 1m 010121").unwrap();
+        ids.insert_str("Phone 31€ 1m 010121").unwrap();
+        let d = crate::date(15, 1, 2021);
+        assert_eq!(ids.income_total(&d), 0.0);
+        assert_eq!(ids.expense_total(&d), ids.cost_of_life(&d));
+        ids.insert_str("Refund -62€ 1m 010121").unwrap();
+        ids.insert_str("Salary -3100€ 1m 010221").unwrap();
+        assert_eq!(ids.expense_total(&d), 11.0);
+        assert_eq!(ids.income_total(&d), -2.0);
+        assert_eq!(ids.cost_of_life(&d), 9.0);
+        let d = crate::date(15, 2, 2021);
+        assert_eq!(ids.expense_total(&d), 0.0);
+        assert_eq!(ids.income_total(&d), -110.71);
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let mut mds = DataStore::new();
+        assert_eq!(mds.last_modified(), None);
+        let records = [
+            "2021-01-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m",
+            "2021-03-01T08:00:00+00:00::2021-01-01::Phone 30€ 1m",
+            "2021-03-01T10:00:00+03:00::2021-01-01::Coffee 2€",
+        ];
+        for r in records.iter() {
+            mds.insert(&TxRecord::from_string_record(r).unwrap());
+        }
+        let expected = DateTime::parse_from_rfc3339("2021-03-01T08:00:00+00:00").unwrap();
+        assert_eq!(mds.last_modified(), Some(expected));
+        let (h, _) = mds.list()[1];
+        mds.remove(&h);
+        assert_eq!(
+            mds.last_modified(),
+            DateTime::parse_from_rfc3339("2021-03-01T10:00:00+03:00").ok()
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let data = [
+            "2021-01-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m",
+            "",
+            "2021-01-01T10:00:00+01:00::2021-01-01::Phone 0€ 1m",
+            "2021-01-01T10:00:00+01:00::2021-01-01::Phone -30€ 1m",
+            "not a record",
+            "2021-01-01T10:00:00+01:00::2021-01-01::30€ 1m #phone",
+            "2021-01-01T10:00:00+01:00::2099-01-01::Phone 30€ 1m",
+            "2999-01-01T10:00:00+01:00::2021-01-01::Gym 30€ 1m",
+            "2021-02-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m #home",
+            "2021-01-01T10:00:00+01:00::2021-31-01::Rent 1000€ 1m",
+        ]
+        .join("\n");
+        let issues = DataStore::lint_str(&data);
+        let lines = issues.iter().map(|i| i.line).collect::<Vec<usize>>();
+        // the negative amount on line 4 is a valid refund
+        assert_eq!(lines, vec![3, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(
+            issues[0].to_string(),
+            "line 3: invalid amount: amount should not be zero: 0.00"
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "line 5: invalid record: not a record"
+        );
+        assert_eq!(issues[2].message, "the name is empty");
+        assert!(issues[3]
+            .message
+            .starts_with("the start date is more than 10 years"));
+        assert!(issues[4]
+            .message
+            .starts_with("the recording time is in the future"));
+        assert_eq!(issues[5].message, "duplicate of line 1");
+        assert!(issues[6].message.starts_with("invalid date format"));
+        assert!(DataStore::lint_str("").is_empty());
+        // the records saved by a datastore are valid
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1100€ 1m 010121 #home").unwrap();
+        ds.insert_str("Phone 30€ 1m 010121").unwrap();
+        let records = ds
+            .list()
+            .iter()
+            .map(|(_, tx)| tx.to_string_record())
+            .collect::<String>();
+        assert!(DataStore::lint_str(&records).is_empty());
+    }
+
+    #[test]
+    fn test_insert_str_on() {
+        let mut ods = DataStore::new();
+        ods.insert_str_on("Rent 310€ 1m", &crate::date(1, 1, 2021))
+            .unwrap();
+        assert_eq!(ods.cost_of_life(&crate::date(31, 1, 2021)), 10.0);
+        assert_eq!(ods.cost_of_life(&crate::date(1, 2, 2021)), 0.0);
+    }
+
+    #[test]
+    fn test_between_amounts() {
+        let mut ads = DataStore::new();
+        ads.insert_str("Rent 1000€ 1m12x 010121").unwrap();
+        ads.insert_str("Phone 30€ 1m12x 010121").unwrap();
+        ads.insert_str("Gym 50€ 1m 010121").unwrap();
+        ads.insert_str("Shoes 200€ 010121").unwrap();
+        ads.insert_str("Coffee 2€ 010121").unwrap();
+        let amount = |v: &str| crate::parse_amount(v).unwrap();
+        let names = |txs: Vec<&TxRecord>| {
+            txs.iter()
+                .map(|tx| tx.get_name().to_string())
+                .collect::<Vec<String>>()
+        };
+        let got = ads.between_amounts(&amount("50"), &amount("200"), false);
+        assert_eq!(names(got), vec!["Gym", "Shoes"]);
+        let got = ads.between_amounts(&amount("50"), &amount("400"), true);
+        assert_eq!(names(got), vec!["Gym", "Shoes", "Phone"]);
+        let got = ads.between_amounts(&amount("0"), &amount("1000000"), false);
+        assert_eq!(names(got), vec!["Coffee", "Phone", "Gym", "Shoes", "Rent"]);
+        assert!(ads
+            .between_amounts(&amount("200"), &amount("50"), false)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_set_tags() {
+        let mut tds = DataStore::new();
+        tds.insert_str("Rent 1000€ 1m 010121 #home").unwrap();
+        let (h, _) = tds.list()[0];
+        let old = tds.set_tags(&h, vec!["flat", "fixed"]).unwrap();
+        assert_eq!(old.get_tags(), vec!["home"]);
+        assert_eq!(tds.size(None), 1);
+        assert_eq!(tds.list()[0].0, h);
+        assert_eq!(tds.list()[0].1.get_tags(), vec!["fixed", "flat"]);
+        assert_eq!(tds.search("flat").len(), 1);
+        assert_eq!(tds.search("home").len(), 0);
+        assert_eq!(
+            tds.audit_log().last().unwrap().op,
+            AuditOp::Update { replaced: h }
+        );
+        assert_eq!(tds.audit_log().last().unwrap().hash, h);
+        assert!(tds
+            .set_tags(&DataStore::hash(&old.with_name("Flat").unwrap()), vec![])
+            .is_none());
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 1100€ 1m 010121 #home").unwrap();
+        let (h, _) = ds.list()[0];
+        let nh = ds.rename(&h, " Flat ").unwrap();
+        assert_ne!(h, nh);
+        assert_eq!(ds.size(None), 1);
+        assert_eq!(ds.find_by_name("Flat").len(), 1);
+        assert_eq!(ds.find_by_name("Flat")[0].get_tags(), vec!["home"]);
+        assert_eq!(ds.list()[0].0, nh);
+        assert_eq!(ds.search("rent").len(), 0);
+        assert_eq!(ds.search("flat").len(), 1);
+        assert_eq!(
+            ds.audit_log().last().unwrap().op,
+            AuditOp::Update { replaced: h }
+        );
+        assert!(ds.rename(&h, "Flat").is_err());
+        assert!(ds.rename(&nh, "  ").is_err());
+        assert_eq!(ds.find_by_name("Flat").len(), 1);
+    }
+
+    #[test]
+    fn test_all() {
+        let mut xds = DataStore::new();
+        assert!(xds.all().is_empty());
+        xds.insert_str("Phone 31€ 1m 150121").unwrap();
+        xds.insert_str("Rent 310€ 1m 010121").unwrap();
+        xds.insert_str("Coffee 2€ 301220").unwrap();
+        xds.insert_str("Car 9000€ 5y 010125").unwrap();
+        xds.insert_str("Bike 300€ 010121").unwrap();
+        let d = crate::date(10, 2, 2021);
+        assert_eq!(
+            xds.all()
+                .iter()
+                .map(|tx| tx.get_name())
+                .collect::<Vec<&str>>(),
+            vec!["Coffee", "Bike", "Rent", "Phone", "Car"]
+        );
+        assert_eq!(xds.all().len(), xds.size(None));
+        // regardless of the state on a date
+        assert!(xds.size(Some(d)) < xds.all().len());
+        assert_eq!(
+            xds.all()
+                .iter()
+                .filter(|tx| tx.is_active_on(&d))
+                .map(|tx| tx.get_name())
+                .collect::<Vec<&str>>(),
+            vec!["Phone"]
+        );
+    }
+
+    #[test]
+    fn test_cost_of_life_with() {
+        let mut ds = DataStore::new();
+        ds.insert_str("Rent 310€ 1m 010121").unwrap();
+        ds.insert_str("Phone 31€ 1m 150121").unwrap();
+        let d = crate::date(31, 1, 2021);
+        assert_eq!(ds.cost_of_life_with(&d, true), 11.0);
+        assert_eq!(ds.cost_of_life_with(&d, false), 1.0);
+        assert_eq!(ds.summary_with(&d, None, true).len(), 2);
+        assert_eq!(ds.summary_with(&d, None, false).len(), 1);
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut ds = DataStore::with_cache();
+        ds.insert_str("Rent 310€ 1m 010121").unwrap();
+        let d = crate::date(15, 1, 2021);
+        assert_eq!(ds.cost_of_life(&d), 10.0);
+        assert_eq!(ds.cache.as_ref().unwrap().lock().unwrap().len(), 1);
+        // a cache hit
+        assert_eq!(ds.cost_of_life(&d), 10.0);
+        assert_eq!(ds.cache.as_ref().unwrap().lock().unwrap().len(), 1);
+        assert_eq!(ds.cost_of_life(&crate::date(15, 2, 2021)), 0.0);
+        assert_eq!(ds.cache.as_ref().unwrap().lock().unwrap().len(), 2);
+        // insert invalidates the cache
+        ds.insert_str("Phone 31€ 1m 010121").unwrap();
+        assert_eq!(ds.cache.as_ref().unwrap().lock().unwrap().len(), 0);
+        assert_eq!(ds.cost_of_life(&d), 11.0);
+        // remove invalidates the cache
+        let (h, _) = ds.list()[0];
+        ds.remove(&h);
+        assert_eq!(ds.cache.as_ref().unwrap().lock().unwrap().len(), 0);
+        assert_eq!(ds.cost_of_life(&d), 10.0);
+        // the cache does not prevent sharing the datastore between threads
+        fn _assert_sync<T: Sync>() {}
+        _assert_sync::<DataStore>();
+    }
+
+    #[test]
+    fn test_load() {
         let mut ds = DataStore::new();
         // db path
         let p = Path::new("./testdata/costoflife.data.txt");
         // load
         let r = ds.load(p);
-        assert_eq!(r.is_err(), false);
-        assert_eq!(ds.size(None), 5 as usize);
+        assert!(r.is_ok());
+        assert_eq!(ds.size(None), 5);
     }
 }
@@ -4,23 +4,48 @@
 //! of an expense over a time range.
 //!
 //! [`CostOf.Life`]: http://thecostof.life
+pub mod ledger;
 mod utils;
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
-use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate};
+use bigdecimal::{BigDecimal, FromPrimitive, One, Zero};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, Timelike, Weekday,
+};
 use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use slug::slugify;
-use std::collections::{BTreeSet, HashMap};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::error::Error;
 use std::fmt;
 use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
 // export utils
 pub use utils::*;
 use wasm_bindgen::prelude::*;
 
+/// Maximum size of a lifetime, in years
+const MAX_LIFETIME_YEARS: f64 = 1000.0;
+
+/// Maximum total amount of a transaction (amount times repeats)
+///
+/// Larger amounts are rejected, this keeps the big decimal arithmetic
+/// fast and the conversions to floating point finite
+const MAX_AMOUNT_TOTAL: i64 = 1_000_000_000_000_000;
+
 /// Rounding factor for big decimals
+///
+/// This is the default, the actual rounding of an amount
+/// depends on its currency (see [`currency_exponent`])
 const SCALE: i64 = 2;
 
+/// The metadata key used to write the pauses of a transaction in a spec
+///
+/// Older versions read the pauses as plain metadata, so the
+/// records with pauses can still be loaded by them
+const PAUSED_META_KEY: &str = "paused";
+
 /// Exposes the per diem calculation to wasm
 ///
 /// # Arguments
@@ -31,11 +56,36 @@ const SCALE: i64 = 2;
 #[wasm_bindgen]
 pub fn costoflife_per_diem(s: &str) -> f32 {
     match TxRecord::from_str(s) {
-        Ok(v) => v.per_diem().to_f32().unwrap(),
+        Ok(v) => to_f32_safe(&v.per_diem()),
         Err(_) => -1.0,
     }
 }
 
+/// Exposes the cost of life by tag to wasm
+///
+/// # Arguments
+///
+/// * `data` - The ledger records, in the same line format of the data file
+/// * `on` - The date to compute the costs for, in the ddmmyy format
+///
+/// Returns a JSON object mapping each tag to its cost,
+/// the object is empty if the ledger is empty or the date is not valid
+///
+#[wasm_bindgen]
+pub fn costoflife_cost_by_tag(data: &str, on: &str) -> String {
+    serde_json::to_string(&cost_by_tag(data, on)).unwrap_or_else(|_| String::from("{}"))
+}
+
+/// Compute the cost of life by tag of a ledger for [`costoflife_cost_by_tag`]
+fn cost_by_tag(data: &str, on: &str) -> BTreeMap<String, f32> {
+    let mut ds = ledger::DataStore::new();
+    ds.load_str(data);
+    match utils::date_from_str(on) {
+        Some(d) => ds.cost_by_tag(&d),
+        None => BTreeMap::new(),
+    }
+}
+
 /// A simple wasm function for testing
 ///
 /// Always return 42.0
@@ -57,7 +107,12 @@ pub enum CostOfLifeError {
 
 impl fmt::Display for CostOfLifeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self)
+        match self {
+            Self::InvalidLifetimeFormat(m) => write!(f, "invalid lifetime format: {}", m),
+            Self::InvalidDateFormat(m) => write!(f, "invalid date format: {}", m),
+            Self::InvalidAmount(m) => write!(f, "invalid amount: {}", m),
+            Self::GenericError(m) => write!(f, "{}", m),
+        }
     }
 }
 
@@ -71,25 +126,132 @@ impl Error for CostOfLifeError {}
 
 // initialize regexp
 lazy_static! {
-    static ref RE_CURRENCY: Regex = Regex::new(r"(\d+(\.\d{2})?)\p{Currency_Symbol}").unwrap();
-    static ref RE_HASHTAG: Regex = Regex::new(r"^[#\.]([a-zA-Z][0-9a-zA-Z_-]*)$").unwrap();
-    static ref RE_LIFETIME: Regex =
-        Regex::new(r"(([1-9]{1}[0-9]*)([dwmy]))(([1-9]{1}[0-9]*)x)?").unwrap();
+    static ref RE_CURRENCY: Regex =
+        Regex::new(r"(?:^|[^0-9.,])((?:^-)?\d+(\.\d+)?)(\p{Currency_Symbol}|[A-Z]{3}\b)").unwrap();
+    static ref RE_HASHTAG: Regex =
+        Regex::new(r#"^[#\.](?:"([^"]+)"|\[([^\]]+)\]|(\p{L}[\p{L}\p{M}\p{N}_-]*))$"#).unwrap();
+    static ref RE_LIFETIME: Regex = Regex::new(
+        r"(?i)(([1-9]{1}[0-9]*)(days?|weeks?|months?|years?|[dwmy]))(([1-9]{1}[0-9]*)x|\+)?"
+    )
+    .unwrap();
+    static ref RE_LIFETIME_LONG: Regex =
+        Regex::new(r"(?i)\b([1-9][0-9]*)\s+(days?|weeks?|months?|years?)(?:\s+([1-9][0-9]*x))?\b")
+            .unwrap();
+    static ref RE_RELATIVE_DATE: Regex = Regex::new(r"^\+([1-9][0-9]*[dwmy])$").unwrap();
     static ref RE_DATE: Regex = Regex::new(r"([0-3][0-9][0-1][0-9][1-9][0-9])").unwrap();
+    static ref RE_DATE_RANGE: Regex =
+        Regex::new(r"^([0-3][0-9][0-1][0-9][1-9][0-9])?\.\.([0-3][0-9][0-1][0-9][1-9][0-9])$")
+            .unwrap();
+    static ref RE_NTH_WEEKDAY: Regex =
+        Regex::new(r"(?i)^([1-5])(?:st|nd|rd|th)-(mon|tue|wed|thu|fri|sat|sun)$").unwrap();
+    static ref RE_SHARE: Regex = Regex::new(r"^/([1-9][0-9]*)$").unwrap();
+    static ref RE_CATEGORY: Regex = Regex::new(r"^::(\p{L}[\p{L}\p{M}\p{N}_-]*)$").unwrap();
+    static ref RE_META: Regex = Regex::new(r"^([a-zA-Z][0-9a-zA-Z_-]*)=(\S+)$").unwrap();
+    static ref RE_TOKEN: Regex = Regex::new(r#"[#\.](?:"[^"]*"|\[[^\]]*\])|\S+"#).unwrap();
+}
+
+/// Split a spec string in tokens
+///
+/// Tokens are separated by whitespaces, except for quoted (`#"home office"`)
+/// or bracketed (`#[home office]`) tags that are kept together
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    RE_TOKEN.find_iter(text).map(|m| m.as_str())
+}
+
+/// Split a spec string from its trailing comment (eg. `// cancelled in March`)
+///
+/// The comment starts at the first token beginning with `//`, so
+/// tokens containing `//` (eg. `url=https://example.com`) are not affected.
+/// Returns the spec and the comment, if any and not empty
+fn split_comment(text: &str) -> (&str, Option<&str>) {
+    match RE_TOKEN
+        .find_iter(text)
+        .find(|m| m.as_str().starts_with("//"))
+    {
+        Some(m) => {
+            let comment = text[m.start() + 2..].trim();
+            (&text[..m.start()], Some(comment).filter(|c| !c.is_empty()))
+        }
+        None => (text, None),
+    }
 }
 
-fn extract_amount(input: &str) -> Option<&str> {
+/// Extract the amount from a token containing a currency symbol
+///
+/// The amount can be embedded in a noisy token (eg. `~20€` or `20€/month`),
+/// but it must not be the tail of a longer number (eg. `01.01.2020€`).
+/// An ISO code must be a known one, so that `2BED` is not an amount.
+/// Returns the amount and the currency (symbol or ISO code)
+fn extract_amount(input: &str) -> Option<(&str, &str)> {
     RE_CURRENCY
-        .captures(input)
-        .and_then(|c| c.get(1).map(|m| m.as_str()))
+        .captures_iter(input)
+        .filter_map(|c| Some((c.get(1)?.as_str(), c.get(3)?.as_str())))
+        // a symbol is a single char, the rest are ISO codes
+        .find(|(_, c)| c.chars().count() == 1 || is_currency_code(c))
+}
+
+/// Tells if a spec string has an amount with the generic currency sign
+/// (eg. `10¤`), that is parsed in the default currency
+///
+/// # Arguments
+///
+/// * `s` - The spec string
+pub fn has_generic_currency(s: &str) -> bool {
+    tokenize(split_comment(s).0)
+        .filter_map(extract_amount)
+        .any(|(_, c)| c == GENERIC_CURRENCY)
 }
 
+/// Extract the tag label from a token
+///
+/// The label of quoted and bracketed tags can contain spaces
 fn extract_hashtag(text: &str) -> Option<&str> {
-    RE_HASHTAG
+    RE_HASHTAG.captures(text).and_then(|c| {
+        c.get(1)
+            .or_else(|| c.get(2))
+            .or_else(|| c.get(3))
+            .map(|m| m.as_str().trim())
+    })
+}
+
+/// Extract a metadata key and value from a `key=value` token
+fn extract_meta(text: &str) -> Option<(&str, &str)> {
+    RE_META
+        .captures(text)
+        .and_then(|c| Some((c.get(1)?.as_str(), c.get(2)?.as_str())))
+}
+
+/// Extract the number of parts from a share token (eg. `/3`)
+fn extract_share(text: &str) -> Option<i64> {
+    RE_SHARE
+        .captures(text)
+        .and_then(|c| c.get(1)?.as_str().parse::<i64>().ok())
+}
+
+/// Extract the category from a token (eg. `::Housing`)
+fn extract_category(text: &str) -> Option<&str> {
+    RE_CATEGORY
         .captures(text)
         .and_then(|c| c.get(1).map(|m| m.as_str()))
 }
 
+/// Format a tag label so that it can be parsed back
+pub fn format_hashtag(tag: &str) -> String {
+    match tag.contains(char::is_whitespace) {
+        true => format!("#\"{}\"", tag),
+        false => format!("#{}", tag),
+    }
+}
+
+/// Get the slug of a tag, that is the key tags are compared by
+///
+/// The tag is normalized to the unicode NFC form before, so that
+/// the same tag written with combining accents or with precomposed
+/// characters (eg. `café`) has the same slug
+pub fn tag_slug(tag: &str) -> String {
+    slugify(tag.nfc().collect::<String>())
+}
+
 fn extract_date(text: &str) -> Option<NaiveDate> {
     let ds = RE_DATE
         .captures(text)
@@ -100,20 +262,228 @@ fn extract_date(text: &str) -> Option<NaiveDate> {
     }
 }
 
+/// Extract the optional start date and the end date from a
+/// date range token (eg. `010122..311223` or `..311223`)
+fn extract_date_range(text: &str) -> Option<(Option<NaiveDate>, NaiveDate)> {
+    let c = RE_DATE_RANGE.captures(text)?;
+    let end = utils::date_from_str(c.get(2)?.as_str())?;
+    match c.get(1) {
+        Some(start) => Some((Some(utils::date_from_str(start.as_str())?), end)),
+        None => Some((None, end)),
+    }
+}
+
+/// Extract the pauses from the value of a `paused` metadata,
+/// a comma separated list of date ranges (eg. `010321..300421,011121..301121`)
+fn extract_pauses(text: &str) -> Result<Vec<(NaiveDate, NaiveDate)>> {
+    text.split(',')
+        .map(|r| match extract_date_range(r) {
+            Some((Some(from), to)) => Ok((from, to)),
+            _ => Err(CostOfLifeError::InvalidDateFormat(format!(
+                "invalid pause: {}",
+                r
+            ))),
+        })
+        .collect()
+}
+
+/// Build a date, moving the day back to the last day
+/// of the month if it does not exists (eg. 31/02 becomes 28/02)
+fn date_clamped(y: i32, m: u32, d: u32) -> NaiveDate {
+    (28..=d)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(y, m, d))
+        .unwrap_or_else(|| NaiveDate::from_ymd(y, m, d))
+}
+
+/// Find a lifetime written in long form (eg. `1 month 12x`) in the tokens of a spec
+///
+/// The amount, the unit and the repeats must be standalone tokens, so
+/// quoted tags (eg. `#"3 days trip"`) are not affected. Returns the position
+/// of the first token, the number of tokens and the lifetime joined
+/// in a single token (eg. `1month12x`)
+fn find_long_lifetime(tokens: &[&str]) -> Option<(usize, usize, String)> {
+    (0..tokens.len()).find_map(|i| {
+        [3, 2].iter().find_map(|&n| {
+            let text = tokens.get(i..i + n)?.join(" ");
+            match is_full_match(&RE_LIFETIME_LONG, &text) {
+                true => Some((i, n, RE_LIFETIME_LONG.replace(&text, "$1$2$3").into_owned())),
+                false => None,
+            }
+        })
+    })
+}
+
+/// Extract the unit, the amount and the repeats of a lifetime
+///
+/// The unit can be in uppercase or in long form (eg. `1M`, `3months`)
 fn extract_lifetime(text: &str) -> (&str, i64, i64) {
     match RE_LIFETIME.captures(text) {
         Some(c) => (
             c.get(3).map_or("d", |unit| unit.as_str()),
-            c.get(2).map_or(1, |a| a.as_str().parse::<i64>().unwrap()),
-            c.get(5).map_or(1, |r| r.as_str().parse::<i64>().unwrap()),
+            // numbers that do not fit are rejected by the lifetime cap
+            c.get(2)
+                .map_or(1, |a| a.as_str().parse::<i64>().unwrap_or(i64::MAX)),
+            c.get(5)
+                .map_or(1, |r| r.as_str().parse::<i64>().unwrap_or(i64::MAX)),
         ),
         None => ("d", 1, 1),
     }
 }
 
+/// Extract the start date from a relative offset token (eg. `+30d`)
+///
+/// The offset is applied to the today date, it supports the same units of a lifetime
+fn extract_relative_date(text: &str, today: &NaiveDate) -> Result<Option<NaiveDate>> {
+    match RE_RELATIVE_DATE.captures(text).and_then(|c| c.get(1)) {
+        Some(offset) => {
+            let days = offset.as_str().parse::<Lifetime>()?.get_days_since(today);
+            Ok(Some(*today + Duration::days(days)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Extract the start date from a nth weekday token (eg. `1st-mon`)
+///
+/// The date is the nth weekday of the month of the today date,
+/// returns an error if the month does not have it (eg. `5th-mon`)
+fn extract_nth_weekday(text: &str, today: &NaiveDate) -> Result<Option<NaiveDate>> {
+    let (n, weekday) = match RE_NTH_WEEKDAY.captures(text) {
+        Some(c) => (c[1].parse::<u32>(), c[2].parse::<Weekday>()),
+        None => return Ok(None),
+    };
+    match (n, weekday) {
+        (Ok(n), Ok(weekday)) => utils::nth_weekday(today.year(), today.month(), weekday, n)
+            .map(Some)
+            .ok_or_else(|| {
+                CostOfLifeError::InvalidDateFormat(format!(
+                    "there is no {} in {}",
+                    text,
+                    today.format("%B %Y")
+                ))
+            }),
+        _ => Ok(None),
+    }
+}
+
+/// The role of a token of a spec
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Amount(&'a str, &'a str), // amount, currency
+    Share(i64),
+    Tag(&'a str),
+    Category(&'a str),
+    Pauses(Vec<(NaiveDate, NaiveDate)>),
+    Meta(&'a str, &'a str),
+    StartDate(NaiveDate),
+    Lifetime(Lifetime),
+    DateRange(Option<NaiveDate>, NaiveDate),
+    Word(&'a str),
+}
+
+/// Tells if a regex matches the whole text
+fn is_full_match(re: &Regex, text: &str) -> bool {
+    matches!(re.find(text), Some(m) if m.as_str() == text)
+}
+
+impl<'a> Token<'a> {
+    /// Classify a token of a spec
+    ///
+    /// Each token has exactly one role, the roles are tried in this order:
+    /// date, amount, share, tag, category, metadata, start date (relative
+    /// or nth weekday), lifetime and date range; anything else is a word of the name.
+    /// The date goes first so that a token of six digits (eg. `010118`)
+    /// is always a date, whatever the other patterns match.
+    ///
+    /// A token that fits more than one role or that looks like a role
+    /// but is not valid is an error instead of a word of the name:
+    /// * an amount that is also a metadata (eg. `deposit=100€`)
+    /// * a tag prefix (`#` or `.`) in front of another role (eg. `#1m` or `.2d`)
+    /// * a date that does not exist (eg. `310221`)
+    fn classify(t: &'a str, today: &NaiveDate) -> Result<Token<'a>> {
+        if is_full_match(&RE_DATE, t) {
+            return extract_date(t).map(Token::StartDate).ok_or_else(|| {
+                CostOfLifeError::InvalidDateFormat(format!("the date does not exist: {}", t))
+            });
+        }
+        if let Some((a, c)) = extract_amount(t) {
+            if RE_META.is_match(t) {
+                return Err(CostOfLifeError::InvalidAmount(format!(
+                    "ambiguous token, it is both an amount and a metadata: {}",
+                    t
+                )));
+            }
+            return Ok(Token::Amount(a, c));
+        }
+        if RE_SHARE.is_match(t) {
+            return extract_share(t)
+                .map(Token::Share)
+                .ok_or_else(|| CostOfLifeError::GenericError(format!("invalid share: {}", t)));
+        }
+        if let Some(tag) = extract_hashtag(t) {
+            return Ok(Token::Tag(tag));
+        }
+        if let Some(c) = extract_category(t) {
+            return Ok(Token::Category(c));
+        }
+        match extract_meta(t) {
+            Some((PAUSED_META_KEY, v)) => return Ok(Token::Pauses(extract_pauses(v)?)),
+            Some((k, v)) => return Ok(Token::Meta(k, v)),
+            None => {}
+        }
+        if let Some(d) = extract_relative_date(t, today)? {
+            return Ok(Token::StartDate(d));
+        }
+        if let Some(d) = extract_nth_weekday(t, today)? {
+            return Ok(Token::StartDate(d));
+        }
+        if is_full_match(&RE_LIFETIME, t) {
+            return Ok(Token::Lifetime(t.parse::<Lifetime>()?));
+        }
+        if RE_DATE_RANGE.is_match(t) {
+            return extract_date_range(t)
+                .map(|(start, end)| Token::DateRange(start, end))
+                .ok_or_else(|| {
+                    CostOfLifeError::InvalidDateFormat(format!("invalid date range: {}", t))
+                });
+        }
+        // a tag prefix in front of another role is ambiguous,
+        // in front of a word it is part of the word (eg. `.5` or `#1`)
+        if let Some(rest) = t.strip_prefix(&['#', '.'][..]) {
+            if !matches!(Token::classify(rest, today), Ok(Token::Word(_))) {
+                return Err(CostOfLifeError::GenericError(format!(
+                    "invalid tag, tags must start with a letter: {}",
+                    t
+                )));
+            }
+        }
+        Ok(Token::Word(t))
+    }
+}
+
+/// Set a value of a spec that can be set only once
+///
+/// Returns the error built by `err` if the value was already set
+fn set_once<T>(
+    slot: &mut Option<T>,
+    value: T,
+    err: impl FnOnce() -> CostOfLifeError,
+) -> Result<()> {
+    if slot.is_some() {
+        return Err(err());
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
 /// A time range with duration and repetition
 ///
-#[derive(Debug, Copy, Clone)]
+/// The `Indefinite` lifetime repeats its period until cancelled,
+/// (eg. `1m+`), a transaction with it is active on every day from
+/// its start date on. The lifetime itself is a single period, the
+/// transaction rolls it over to cover the current date (see [`TxRecord::get_ends_on`])
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum Lifetime {
     // amount, times
     SingleDay,
@@ -121,6 +491,7 @@ pub enum Lifetime {
     Month { amount: i64, times: i64 },
     Week { amount: i64, times: i64 },
     Day { amount: i64, times: i64 },
+    Indefinite { amount: i64, period: Period },
 }
 
 impl Lifetime {
@@ -131,26 +502,25 @@ impl Lifetime {
     pub fn get_days_since(&self, since: &NaiveDate) -> i64 {
         match self {
             Self::Month { amount, times } => {
-                // compute the total number of months (nm)
-                let nm = since.month() + (times * amount) as u32;
+                // compute the total number of months (nm), zero based
+                let nm = since.month0() + (times * amount) as u32;
                 // match nm (number of months) and calculate the end year / month
-                let (y, m) = (since.year() as u32 + nm / 12, nm % 12);
-                // wrap the result with the correct type
-                let (y, m, d) = (y as i32, m, since.day());
+                let (y, m) = (since.year() as u32 + nm / 12, nm % 12 + 1);
                 // calculate the end date
-                let end = NaiveDate::from_ymd(y, m, d);
+                let end = date_clamped(y as i32, m, since.day());
                 // count the days
                 end.signed_duration_since(*since).num_days()
             }
             Self::Year { amount, times } => {
                 let ny = since.year() + (times * amount) as i32;
-                let end = NaiveDate::from_ymd(ny, since.month(), since.day());
+                let end = date_clamped(ny, since.month(), since.day());
                 // count the days
                 end.signed_duration_since(*since).num_days()
             }
             Self::Week { amount, times } => amount * 7 * times,
             Self::Day { amount, times } => amount * times,
             Self::SingleDay => 1,
+            Self::Indefinite { .. } => self.with_times(1).get_days_since(since),
         }
     }
 
@@ -163,17 +533,44 @@ impl Lifetime {
     ///
     fn get_days_approx(&self) -> f64 {
         match self {
-            Self::Year { amount, times } => 365.25 * (amount * times) as f64,
-            Self::Month { amount, times } => 30.44 * (amount * times) as f64,
-            Self::Week { amount, times } => 7.0 * (amount * times) as f64,
-            Self::Day { amount, times } => (amount * times) as f64,
+            Self::Year { amount, times } => 365.25 * *amount as f64 * *times as f64,
+            Self::Month { amount, times } => 30.44 * *amount as f64 * *times as f64,
+            Self::Week { amount, times } => 7.0 * *amount as f64 * *times as f64,
+            Self::Day { amount, times } => *amount as f64 * *times as f64,
             Self::SingleDay => 1.0,
+            Self::Indefinite { .. } => self.with_times(1).get_days_approx(),
         }
     }
 
-    /// Get the number of duration repeats for the current lifetime
+    /// Get how many of another lifetime this lifetime spans,
+    /// to compare lifetimes of different cadences
+    ///
+    /// # Arguments
+    ///
+    /// * `unit_of` - The lifetime to use as the unit, repetitions included
+    ///
+    /// The result is approximate since a year is counted as 365.25 days
+    /// and a month as 30.44 days, eg. `1m` is about 4.35 weeks
+    /// and `1y` is about 12 months, not exactly 12.
+    /// A single day is one day and an indefinite lifetime is a single period
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use costoflife::Lifetime;
+    /// use std::str::FromStr;
     ///
+    /// let month = Lifetime::from_str("1m").unwrap();
+    /// let weeks = month.equivalent_in(&Lifetime::from_str("1w").unwrap());
+    /// assert_eq!(format!("{:.2}", weeks), "4.35");
+    /// ```
+    pub fn equivalent_in(&self, unit_of: &Lifetime) -> f64 {
+        self.get_days_approx() / unit_of.get_days_approx()
+    }
+
+    /// Get the number of duration repeats for the current lifetime
     ///
+    /// An indefinite lifetime counts as a single repeat
     pub fn get_repeats(&self) -> i64 {
         match self {
             Self::Year { times, .. } => *times,
@@ -181,7 +578,185 @@ impl Lifetime {
             Self::Day { times, .. } => *times,
             Self::Month { times, .. } => *times,
             Self::SingleDay => 1,
+            Self::Indefinite { .. } => 1,
+        }
+    }
+
+    /// Tells if the lifetime repeats until cancelled
+    pub fn is_indefinite(&self) -> bool {
+        matches!(self, Self::Indefinite { .. })
+    }
+
+    /// Returns the lifetime that repeats the same period until cancelled
+    fn until_cancelled(&self) -> Lifetime {
+        match *self {
+            Self::Year { amount, .. } => Self::Indefinite {
+                amount,
+                period: Period::Year,
+            },
+            Self::Month { amount, .. } => Self::Indefinite {
+                amount,
+                period: Period::Month,
+            },
+            Self::Week { amount, .. } => Self::Indefinite {
+                amount,
+                period: Period::Week,
+            },
+            Self::Day { amount, .. } => Self::Indefinite {
+                amount,
+                period: Period::Day,
+            },
+            Self::SingleDay => Self::Indefinite {
+                amount: 1,
+                period: Period::Day,
+            },
+            lifetime => lifetime,
+        }
+    }
+
+    /// Build a lifetime that repeats a period from a start to an end date
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The start date
+    /// * `end` - The end date (included)
+    /// * `period` - The period to repeat, its repetitions are ignored
+    ///   (an indefinite period is repeated until the end date)
+    ///
+    /// The number of repetitions is rounded up, so that a partial
+    /// final period is counted as a full one (eg. from 01/01 to 15/02
+    /// with a monthly period gives `1m2x`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use costoflife::{self, Lifetime};
+    ///
+    /// let lt = Lifetime::from_start_end_period(
+    ///     &costoflife::date(1, 1, 2022),
+    ///     &costoflife::date(31, 12, 2023),
+    ///     &Lifetime::Month {
+    ///         amount: 1,
+    ///         times: 1,
+    ///     },
+    /// ).unwrap();
+    /// assert_eq!(lt.to_string(), "1m24x");
+    /// ```
+    pub fn from_start_end_period(
+        start: &NaiveDate,
+        end: &NaiveDate,
+        period: &Lifetime,
+    ) -> Result<Lifetime> {
+        if end < start {
+            return Err(CostOfLifeError::InvalidLifetimeFormat(format!(
+                "the end date {} is before the start date {}",
+                end, start
+            )));
+        }
+        // the number of days to cover
+        let days = (*end - *start).num_days() + 1;
+        // start from an estimate of the repetitions
+        let mut times = ((days as f64 / period.with_times(1).get_days_approx()) as i64 - 1).max(1);
+        loop {
+            let lifetime = period.with_times(times);
+            if lifetime.get_days_approx() > MAX_LIFETIME_YEARS * 365.25 {
+                return Err(CostOfLifeError::InvalidLifetimeFormat(format!(
+                    "lifetime {} exceeds the maximum of {} years",
+                    lifetime, MAX_LIFETIME_YEARS
+                )));
+            }
+            if lifetime.get_days_since(start) >= days {
+                return Ok(lifetime);
+            }
+            times += 1;
+        }
+    }
+
+    /// Returns the same period of the lifetime with different repetitions
+    fn with_times(&self, times: i64) -> Lifetime {
+        match *self {
+            Self::Year { amount, .. } => Self::Year { amount, times },
+            Self::Month { amount, .. } => Self::Month { amount, times },
+            Self::Week { amount, .. } => Self::Week { amount, times },
+            Self::Day { amount, .. } => Self::Day { amount, times },
+            Self::SingleDay => Self::Day { amount: 1, times },
+            Self::Indefinite { amount, period } => match period {
+                Period::Year => Self::Year { amount, times },
+                Period::Month => Self::Month { amount, times },
+                Period::Week => Self::Week { amount, times },
+                Period::Day => Self::Day { amount, times },
+            },
+        }
+    }
+
+    /// Returns the canonical form of the lifetime
+    ///
+    /// Equivalent lifetimes have the same canonical form, the rules are:
+    /// - `SingleDay` becomes `Day { amount: 1, times: 1 }`
+    /// - days that are a multiple of 7 become weeks (eg. `14d` -> `2w`)
+    /// - months that are a multiple of 12 become years (eg. `24m` -> `2y`)
+    /// - indefinite lifetimes follow the rules of their period (eg. `14d+` -> `2w+`)
+    ///
+    /// The `times` are never folded in the `amount` since the amount
+    /// of a transaction is charged for each repetition
+    /// (eg. `1m12x` is not the same as `1y`)
+    pub fn normalize(&self) -> Lifetime {
+        match *self {
+            Self::SingleDay => Self::Day {
+                amount: 1,
+                times: 1,
+            },
+            Self::Day { amount, times } if amount % 7 == 0 => Self::Week {
+                amount: amount / 7,
+                times,
+            },
+            Self::Month { amount, times } if amount % 12 == 0 => Self::Year {
+                amount: amount / 12,
+                times,
+            },
+            Self::Indefinite { .. } => self.with_times(1).normalize().until_cancelled(),
+            lifetime => lifetime,
+        }
+    }
+
+    /// Build a lifetime from a duration
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The duration, rounded down to whole days
+    ///
+    /// The lifetime uses the coarsest exact unit, weeks if the
+    /// duration is a whole number of weeks, days otherwise.
+    /// Durations shorter than a day and longer than the maximum
+    /// lifetime are an error
+    ///
+    /// ```
+    /// use chrono::Duration;
+    /// use costoflife::Lifetime;
+    ///
+    /// let lifetime = Lifetime::from_duration(Duration::days(14)).unwrap();
+    /// assert_eq!(lifetime, Lifetime::Week { amount: 2, times: 1 });
+    /// ```
+    pub fn from_duration(d: Duration) -> Result<Lifetime> {
+        let days = d.num_days();
+        if days <= 0 {
+            return Err(CostOfLifeError::InvalidLifetimeFormat(format!(
+                "the duration should be at least one day: {}",
+                d
+            )));
         }
+        let lifetime = Self::Day {
+            amount: days,
+            times: 1,
+        }
+        .normalize();
+        if lifetime.get_days_approx() > MAX_LIFETIME_YEARS * 365.25 {
+            return Err(CostOfLifeError::InvalidLifetimeFormat(format!(
+                "duration of {} days exceeds the maximum of {} years",
+                days, MAX_LIFETIME_YEARS
+            )));
+        }
+        Ok(lifetime)
     }
 }
 
@@ -190,18 +765,33 @@ impl FromStr for Lifetime {
 
     fn from_str(s: &str) -> Result<Lifetime> {
         let (period, amount, times) = extract_lifetime(s);
-        match period {
-            "w" => Ok(Lifetime::Week { amount, times }),
-            "y" => Ok(Lifetime::Year { amount, times }),
-            "m" => Ok(Lifetime::Month { amount, times }),
-            _ => Ok(Lifetime::Day { amount, times }),
+        // the unit is the first letter, in any case
+        let lifetime = match period.chars().next().map(|c| c.to_ascii_lowercase()) {
+            Some('w') => Lifetime::Week { amount, times },
+            Some('y') => Lifetime::Year { amount, times },
+            Some('m') => Lifetime::Month { amount, times },
+            _ => Lifetime::Day { amount, times },
+        };
+        // a trailing + repeats the period until cancelled (eg. `1m+`)
+        let lifetime = match RE_LIFETIME.captures(s).and_then(|c| c.get(4)) {
+            Some(r) if r.as_str() == "+" => lifetime.until_cancelled(),
+            _ => lifetime,
+        };
+        // prevent overflows on date calculations
+        if lifetime.get_days_approx() > MAX_LIFETIME_YEARS * 365.25 {
+            return Err(CostOfLifeError::InvalidLifetimeFormat(format!(
+                "lifetime {} exceeds the maximum of {} years",
+                s, MAX_LIFETIME_YEARS
+            )));
         }
+        Ok(lifetime)
     }
 }
 
 impl PartialEq for Lifetime {
     fn eq(&self, other: &Self) -> bool {
-        self.get_days_approx() == other.get_days_approx()
+        self.is_indefinite() == other.is_indefinite()
+            && self.get_days_approx() == other.get_days_approx()
     }
 }
 
@@ -213,19 +803,95 @@ impl fmt::Display for Lifetime {
             Self::Week { amount, times } => write!(f, "{}w{}x", amount, times),
             Self::Day { amount, times } => write!(f, "{}d{}x", amount, times),
             Self::SingleDay => write!(f, "1d1x"),
+            Self::Indefinite { amount, period } => match period {
+                Period::Year => write!(f, "{}y+", amount),
+                Period::Month => write!(f, "{}m+", amount),
+                Period::Week => write!(f, "{}w+", amount),
+                Period::Day => write!(f, "{}d+", amount),
+            },
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// A period of time to express a cost over
+///
+/// Months and years have their average length,
+/// that is 30.4375 and 365.25 days
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Get the number of days of the period
+    pub fn days(&self) -> BigDecimal {
+        match self {
+            Self::Day => BigDecimal::one(),
+            Self::Week => BigDecimal::from(7),
+            Self::Month => BigDecimal::from_str("30.4375").unwrap(),
+            Self::Year => BigDecimal::from_str("365.25").unwrap(),
+        }
+    }
+}
+
+impl FromStr for Period {
+    type Err = CostOfLifeError;
+
+    fn from_str(s: &str) -> Result<Period> {
+        match s.to_lowercase().as_str() {
+            "day" | "d" => Ok(Self::Day),
+            "week" | "w" => Ok(Self::Week),
+            "month" | "m" => Ok(Self::Month),
+            "year" | "y" => Ok(Self::Year),
+            _ => Err(CostOfLifeError::GenericError(format!(
+                "invalid period, use day, week, month or year: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Day => write!(f, "day"),
+            Self::Week => write!(f, "week"),
+            Self::Month => write!(f, "month"),
+            Self::Year => write!(f, "year"),
+        }
+    }
+}
+
+/// How the amount of a transaction is meant
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AmountKind {
+    /// the amount is charged for each repeat of the lifetime,
+    /// the total is the amount times the repeats (eg. `10€ 1m12x`)
+    PerPeriod,
+    /// the amount is the total of the transaction (eg. `120€ 1y`)
+    Total,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TxRecord {
     name: String,
     tags: HashMap<String, String>,
+    meta: HashMap<String, String>,
     amount: BigDecimal,
+    currency: String,
+    category: Option<String>,
+    note: Option<String>,
     starts_on: NaiveDate,
     lifetime: Lifetime, // in days
     recorded_at: DateTime<FixedOffset>,
     src: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pauses: Vec<(NaiveDate, NaiveDate)>, // sorted, not overlapping
+    #[serde(skip)]
+    per_diem_cache: OnceCell<BigDecimal>, // see per_diem_raw
 }
 
 /// Holds a transaction informations
@@ -245,9 +911,253 @@ impl TxRecord {
             .into_iter()
             .collect()
     }
-    /// Get the amount for the tx, rounded to 2 decimals
+    /// Get the value of a metadata key for the tx
+    pub fn get_meta(&self, key: &str) -> Option<&str> {
+        self.meta.get(key).map(|v| &v[..])
+    }
+    /// Get the metadata for the tx as (key, value) pairs, sorted by key
+    pub fn get_metadata(&self) -> Vec<(String, String)> {
+        let mut meta = self
+            .meta
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<(String, String)>>();
+        meta.sort();
+        meta
+    }
+    /// Get the amount for the tx, rounded to the currency decimals
     pub fn get_amount(&self) -> BigDecimal {
-        self.amount.with_scale(SCALE)
+        self.round_amount(&self.amount)
+    }
+    /// Round an amount to the currency decimals, half up
+    fn round_amount(&self, v: &BigDecimal) -> BigDecimal {
+        round_half_up(v, self.get_scale())
+    }
+    /// Get the amount for the tx as a plain decimal string,
+    /// without the currency symbol (eg. for exports)
+    pub fn get_amount_plain(&self) -> String {
+        self.get_amount().to_string()
+    }
+    /// Returns a copy of the record with only a share of the amount
+    ///
+    /// # Arguments
+    ///
+    /// * `fraction` - The share of the amount, greater than 0 and at most 1
+    ///
+    /// The amount is scaled by the fraction and rounded to the
+    /// nearest currency decimal, the original amount is kept in the
+    /// `full_amount` metadata. The fraction should leave a non zero amount.
+    ///
+    pub fn with_share(&self, fraction: &BigDecimal) -> Result<TxRecord> {
+        if *fraction <= BigDecimal::zero() || *fraction > BigDecimal::one() {
+            return Err(CostOfLifeError::InvalidAmount(format!(
+                "the share should be between 0 and 1: {}",
+                fraction
+            )));
+        }
+        let amount = self.round_amount(&(&self.amount * fraction));
+        if amount.is_zero() {
+            return Err(CostOfLifeError::InvalidAmount(format!(
+                "the share {} of {} is too small",
+                fraction,
+                self.get_amount()
+            )));
+        }
+        let mut tx = self.clone();
+        tx.meta
+            .insert("full_amount".to_string(), self.get_amount_plain());
+        tx.amount = amount;
+        tx.detach();
+        Ok(tx)
+    }
+    /// Returns a copy of the record with a different set of tags
+    ///
+    /// # Arguments
+    ///
+    /// * `tags` - The new tags, they replace the existing ones
+    ///
+    /// The tags are not part of the record identity, so the copy
+    /// has the same hash of the original in a [`ledger::DataStore`]
+    ///
+    pub fn clone_with_tags(&self, tags: Vec<&str>) -> TxRecord {
+        let mut tx = self.clone();
+        tx.tags = tags
+            .iter()
+            .map(|v| (tag_slug(v), v.nfc().collect::<String>()))
+            .collect();
+        tx.detach();
+        tx
+    }
+    /// Returns a copy of the record with a different name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The new name, it cannot be empty
+    ///
+    pub fn with_name(&self, name: &str) -> Result<TxRecord> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(CostOfLifeError::GenericError(
+                "the name cannot be empty".to_string(),
+            ));
+        }
+        let mut tx = self.clone();
+        tx.name = name.to_string();
+        tx.detach();
+        Ok(tx)
+    }
+    /// Merge the record with another record of the same recurring expense
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The record to merge with
+    ///
+    /// Two records can be merged if they have the same name, amount,
+    /// currency and lifetime period (eg. both `1m`) and their date ranges
+    /// overlap or are contiguous. The records that repeat until cancelled
+    /// or that have pauses are never merged, `None` is returned otherwise.
+    ///
+    /// The merged record starts on the earliest start date and repeats the
+    /// period until the latest end date, a partial final period counts
+    /// as a full one (see [`Lifetime::from_start_end_period`]), so the
+    /// overlapping days are counted once. The tags and the metadata are the
+    /// union of the two, the record that starts first wins on the metadata
+    /// with different values, on the category and on the note. The recording
+    /// time is the earliest one.
+    pub fn merge_same(&self, other: &TxRecord) -> Option<TxRecord> {
+        let period = |tx: &TxRecord| tx.lifetime.with_times(1).normalize().to_string();
+        let mergeable = |tx: &TxRecord| !tx.is_indefinite() && tx.pauses.is_empty();
+        if self.name != other.name
+            || self.amount != other.amount
+            || self.currency != other.currency
+            || period(self) != period(other)
+            || !mergeable(self)
+            || !mergeable(other)
+        {
+            return None;
+        }
+        let (first, second) =
+            match (self.starts_on, self.recorded_at) <= (other.starts_on, other.recorded_at) {
+                true => (self, other),
+                false => (other, self),
+            };
+        if second.starts_on > first.get_ends_on() + Duration::days(1) {
+            return None;
+        }
+        let ends_on = max(first.get_ends_on(), second.get_ends_on());
+        let mut tx = first.clone();
+        tx.lifetime =
+            Lifetime::from_start_end_period(&first.starts_on, &ends_on, &first.lifetime).ok()?;
+        second.tags.iter().for_each(|(k, v)| {
+            tx.tags.entry(k.clone()).or_insert_with(|| v.clone());
+        });
+        second.meta.iter().for_each(|(k, v)| {
+            tx.meta.entry(k.clone()).or_insert_with(|| v.clone());
+        });
+        tx.category = first.category.clone().or_else(|| second.category.clone());
+        tx.note = first.note.clone().or_else(|| second.note.clone());
+        tx.recorded_at = min(first.recorded_at, second.recorded_at);
+        tx.detach();
+        if tx.get_amount_total().abs() > BigDecimal::from_i64(MAX_AMOUNT_TOTAL).unwrap() {
+            return None;
+        }
+        Some(tx)
+    }
+    /// Get the category of the tx, if any
+    pub fn get_category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+    /// Get the note of the tx, that is the comment of its spec, if any
+    pub fn get_note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+    /// Get the pauses of the tx as date ranges, bounds included,
+    /// sorted by start date
+    pub fn get_pauses(&self) -> &[(NaiveDate, NaiveDate)] {
+        &self.pauses
+    }
+    /// Tells if the tx is paused on a date
+    pub fn is_paused_on(&self, target: &NaiveDate) -> bool {
+        self.pauses
+            .iter()
+            .any(|(from, to)| from <= target && target <= to)
+    }
+    /// Pause the transaction for a date range
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The first paused day
+    /// * `to` - The last paused day (included)
+    ///
+    /// The paused days are not active and they do not count for the
+    /// per diem, see [`TxRecord::per_diem_raw`]. Overlapping pauses are
+    /// merged, the pauses cannot cover all the days of the transaction
+    ///
+    pub fn add_pause(&mut self, from: NaiveDate, to: NaiveDate) -> Result<()> {
+        self.insert_pause(from, to)?;
+        self.detach();
+        Ok(())
+    }
+    /// Add a pause to the sorted pauses, merging the overlapping ones
+    fn insert_pause(&mut self, from: NaiveDate, to: NaiveDate) -> Result<()> {
+        if from > to {
+            return Err(CostOfLifeError::InvalidDateFormat(format!(
+                "the pause should end after it starts: {}..{}",
+                from, to
+            )));
+        }
+        let mut pauses = self.pauses.clone();
+        pauses.push((from, to));
+        pauses.sort();
+        let mut merged: Vec<(NaiveDate, NaiveDate)> = Vec::with_capacity(pauses.len());
+        for (from, to) in pauses {
+            match merged.last_mut() {
+                Some(last) if from <= last.1 + Duration::days(1) => last.1 = max(last.1, to),
+                _ => merged.push((from, to)),
+            }
+        }
+        let before = std::mem::replace(&mut self.pauses, merged);
+        self.clear_per_diem();
+        if self.get_active_days() <= 0 {
+            self.pauses = before;
+            self.clear_per_diem();
+            return Err(CostOfLifeError::GenericError(format!(
+                "the pause {}..{} leaves no active days",
+                from, to
+            )));
+        }
+        Ok(())
+    }
+    /// Get the pauses as a spec token, if there are any
+    /// (eg. `paused=010321..300421,011121..301121`)
+    fn pauses_spec(&self) -> Option<String> {
+        if self.pauses.is_empty() {
+            return None;
+        }
+        let ranges = self
+            .pauses
+            .iter()
+            .map(|(from, to)| format!("{}..{}", from.format("%d%m%y"), to.format("%d%m%y")))
+            .collect::<Vec<String>>();
+        Some(format!("{}={}", PAUSED_META_KEY, ranges.join(",")))
+    }
+    /// Get the number of paused days within a date range, bounds included
+    fn paused_days_between(&self, from: &NaiveDate, to: &NaiveDate) -> i64 {
+        self.pauses
+            .iter()
+            .map(|(p_from, p_to)| {
+                let days = (min(*p_to, *to) - max(*p_from, *from)).num_days() + 1;
+                days.max(0)
+            })
+            .sum()
+    }
+    /// Get the ISO 4217 code of the currency for the tx
+    pub fn get_currency(&self) -> &str {
+        &self.currency[..]
+    }
+    /// Get the number of decimals used to round the amounts of the tx
+    fn get_scale(&self) -> i64 {
+        currency_exponent(&self.currency)
     }
     /// Get the lifetime for the tx
     pub fn get_lifetime(&self) -> &Lifetime {
@@ -267,40 +1177,185 @@ impl TxRecord {
     }
     /// Returns true if the base amount is the same as the total
     ///
-    /// That is, when there is no repetition on the lifetime
+    /// Despite the name it is true when the lifetime repeats, that is
+    /// when the amount is per period, prefer [`TxRecord::amount_kind`].
+    /// For an indefinite lifetime only the first repeat is considered
     pub fn amount_is_total(&self) -> bool {
-        self.lifetime.get_repeats() > 1
+        self.lifetime_at(&self.starts_on).get_repeats() > 1
+    }
+    /// Returns how the amount of the transaction is meant
+    ///
+    /// The amount is per period when the lifetime repeats, that is
+    /// with more than one repeat or until cancelled, it is the total otherwise
+    pub fn amount_kind(&self) -> AmountKind {
+        match self.is_indefinite() || self.lifetime.get_repeats() > 1 {
+            true => AmountKind::PerPeriod,
+            false => AmountKind::Total,
+        }
     }
     /// Tells if the TxRecord as a tag
     pub fn has_tag(&self, tag: &str) -> bool {
-        self.tags.contains_key(&slugify(&tag))
+        self.tags.contains_key(&tag_slug(tag))
     }
     /// Returns total amount for the transaction record
+    ///
+    /// The total is the amount times the lifetime repeats,
+    /// it never exceeds 10^15 (see `MAX_AMOUNT_TOTAL`).
+    /// For an indefinite lifetime it is the amount of the
+    /// first repeat, see [`TxRecord::get_amount_total_at`]
     pub fn get_amount_total(&self) -> BigDecimal {
-        BigDecimal::from_i64(self.lifetime.get_repeats()).unwrap() * &self.amount
+        self.get_amount_total_at(&self.starts_on)
+    }
+    /// Returns total amount for the transaction record at a date
+    ///
+    /// For an indefinite lifetime it is the total of the repeats
+    /// up to the one of the date, otherwise it is the same
+    /// as [`TxRecord::get_amount_total`]
+    pub fn get_amount_total_at(&self, d: &NaiveDate) -> BigDecimal {
+        BigDecimal::from_i64(self.lifetime_at(d).get_repeats()).unwrap() * &self.amount
     }
     /// Returns the duration in days for this transaction
+    ///
+    /// This is the number of days from the start date to the
+    /// end date, both included, paused days included
     pub fn get_duration_days(&self) -> i64 {
-        self.lifetime.get_days_since(&self.starts_on)
+        self.get_duration_days_at(&self.starts_on)
+    }
+    /// Returns the duration in days for this transaction at a date,
+    /// see [`TxRecord::get_ends_on_at`]
+    pub fn get_duration_days_at(&self, d: &NaiveDate) -> i64 {
+        self.lifetime_at(d).get_days_since(&self.starts_on)
+    }
+    /// Tells if the transaction repeats until cancelled
+    pub fn is_indefinite(&self) -> bool {
+        self.lifetime.is_indefinite()
+    }
+    /// Returns the lifetime with the indefinite lifetimes rolled over
+    /// to the repeats needed to cover a date
+    ///
+    /// A transaction that starts after the date covers a single period
+    fn lifetime_at(&self, d: &NaiveDate) -> Lifetime {
+        if !self.is_indefinite() {
+            return self.lifetime;
+        }
+        let d = max(*d, self.starts_on);
+        Lifetime::from_start_end_period(&self.starts_on, &d, &self.lifetime)
+            .unwrap_or_else(|_| self.lifetime.with_times(1))
+    }
+    /// Returns the number of days the transaction is active,
+    /// that is the duration minus the paused days
+    pub fn get_active_days(&self) -> i64 {
+        self.active_days_at(&self.starts_on)
+    }
+    /// Returns the number of days the transaction is active at a date,
+    /// see [`TxRecord::get_ends_on_at`]
+    fn active_days_at(&self, d: &NaiveDate) -> i64 {
+        self.get_duration_days_at(d)
+            - self.paused_days_between(&self.starts_on, &self.get_ends_on_at(d))
     }
     /// Calculates and returns the per diem for the record
-    /// and round it to the 2 decimals
+    /// and round it to the currency decimals
     ///
     pub fn per_diem(&self) -> BigDecimal {
-        self.per_diem_raw().with_scale(SCALE)
+        self.round_amount(&self.per_diem_raw())
     }
-    /// Calculates and returns the per diem for the record
+    /// Calculates and returns the per diem for the record at a date
+    /// and round it to the currency decimals, see [`TxRecord::per_diem_raw_at`]
     ///
-    /// The per diem is calculated as follow:
+    pub fn per_diem_at(&self, d: &NaiveDate) -> BigDecimal {
+        self.round_amount(&self.per_diem_raw_at(d))
+    }
+    /// Calculates the cost of the record over a period,
+    /// that is the per diem times the days of the period,
+    /// rounded to the currency decimals
     ///
-    /// * END_DAY = START_DAY + (RECURRENCE_SIZE_DAYS * SEC_IN_DAYS  * RECURRENCE_TIMES)
-    /// * PER_DIEM = AMOUNT * RECURRENCE_TIMES) / (END_DAY - START_DAY )
+    pub fn per_period(&self, period: &Period) -> BigDecimal {
+        self.per_period_at(period, &self.starts_on)
+    }
+    /// Calculates the cost of the record over a period with
+    /// the per diem at a date, see [`TxRecord::per_diem_raw_at`]
     ///
-    pub fn per_diem_raw(&self) -> BigDecimal {
-        let duration_days = BigDecimal::from_i64(self.get_duration_days()).unwrap();
-        self.get_amount_total() / duration_days
+    pub fn per_period_at(&self, period: &Period, d: &NaiveDate) -> BigDecimal {
+        self.round_amount(&(self.per_diem_raw_at(d) * period.days()))
     }
-
+    /// Calculates the cost of the record over a week
+    pub fn per_week(&self) -> BigDecimal {
+        self.per_period(&Period::Week)
+    }
+    /// Calculates the cost of the record over an average month
+    pub fn per_month(&self) -> BigDecimal {
+        self.per_period(&Period::Month)
+    }
+    /// Calculates the cost of the record over an average year
+    pub fn annual_cost(&self) -> BigDecimal {
+        self.per_period(&Period::Year)
+    }
+    /// Calculates and returns the per diem for the record
+    ///
+    /// The per diem is calculated as follow:
+    ///
+    /// * END_DAY = START_DAY + (RECURRENCE_SIZE_DAYS * SEC_IN_DAYS  * RECURRENCE_TIMES)
+    /// * PER_DIEM = AMOUNT * RECURRENCE_TIMES) / (END_DAY - START_DAY - PAUSED_DAYS)
+    ///
+    /// Pausing does not move the end date and it does not change the total
+    /// amount: the total is spread over the active days only, so the per
+    /// diem of the active days grows and the paused days cost nothing.
+    /// For example 300€ over 30 days with 10 paused days is 15€ per diem
+    /// for 20 days, instead of 10€ for 30 days.
+    ///
+    /// The per diem is computed once and cached, the cache is cleared when
+    /// the amount, the start date, the lifetime or the pauses change. The
+    /// per diem of a transaction that repeats until cancelled changes with
+    /// the date, so it is never cached, here it is the one of the first
+    /// repeat, see [`TxRecord::per_diem_raw_at`].
+    ///
+    pub fn per_diem_raw(&self) -> BigDecimal {
+        self.per_diem_raw_at(&self.starts_on)
+    }
+    /// Calculates and returns the per diem for the record at a date
+    ///
+    /// For an indefinite lifetime the total amount is spread over
+    /// the repeats up to the one of the date, otherwise it is
+    /// the same as [`TxRecord::per_diem_raw`]
+    ///
+    pub fn per_diem_raw_at(&self, d: &NaiveDate) -> BigDecimal {
+        if self.is_indefinite() {
+            return self.compute_per_diem(d);
+        }
+        self.per_diem_cache
+            .get_or_init(|| self.compute_per_diem(d))
+            .clone()
+    }
+    /// Compute the per diem at a date, see [`TxRecord::per_diem_raw_at`]
+    fn compute_per_diem(&self, d: &NaiveDate) -> BigDecimal {
+        let active_days = BigDecimal::from_i64(self.active_days_at(d)).unwrap();
+        self.get_amount_total_at(d) / active_days
+    }
+    /// Clear the cached per diem, to call when a field it depends on changes
+    fn clear_per_diem(&mut self) {
+        self.per_diem_cache.take();
+    }
+    /// Detach a derived record from its source spec, that does not
+    /// describe it anymore, and clear the cached per diem
+    fn detach(&mut self) {
+        self.src = None;
+        self.clear_per_diem();
+    }
+    /// Returns the amount attributable to a single date
+    ///
+    /// # Arguments
+    ///
+    /// * `d` - The date
+    ///
+    /// That is the raw per diem if the transaction is active on
+    /// the date (see [`TxRecord::is_active_on`]), zero otherwise
+    pub fn amount_on(&self, d: &NaiveDate) -> BigDecimal {
+        match self.is_active_on(d) {
+            true => self.per_diem_raw_at(d),
+            false => BigDecimal::zero(),
+        }
+    }
+
     /// Get the progress of the transaction at date
     ///
     /// None will use today as a data
@@ -310,7 +1365,7 @@ impl TxRecord {
             None => utils::today(),
         };
         // get the time range
-        let (start, end) = (self.starts_on, self.get_ends_on());
+        let (start, end) = (self.starts_on, self.get_ends_on_at(&d));
         if d <= start {
             // if the tx period has not started
             return 0.0;
@@ -321,52 +1376,269 @@ impl TxRecord {
         }
         // total number of days
         let n = (end - start).num_days() as f32;
+        if n <= 0.0 {
+            // zero length span, it is fully elapsed
+            return 1.0;
+        }
         // number of elapsed days
         let y = (d - start).num_days() as f32;
         // duration percentage
         y / n
     }
 
+    /// Get the date at which the transaction reaches a progress
+    ///
+    /// It is the inverse of `get_progress`, the date is interpolated
+    /// linearly between the start and the end date and rounded to the
+    /// closest day. Returns None if the fraction is not within 0 and 1
+    ///
+    /// # Arguments
+    ///
+    /// * `fraction` - the progress, between 0.0 and 1.0
+    pub fn date_at_progress(&self, fraction: f64) -> Option<NaiveDate> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return None;
+        }
+        let n = (self.get_ends_on() - self.starts_on).num_days() as f64;
+        let y = (n * fraction).round() as i64;
+        Some(self.starts_on + Duration::days(y))
+    }
+
+    /// Get the number of active days of the transaction elapsed at date,
+    /// the date itself included
+    ///
+    /// None will use today as a date.
+    /// The result is 0 before the start date and the
+    /// active days of the transaction after the end date,
+    /// the paused days are not counted
+    pub fn days_elapsed(&self, d: Option<NaiveDate>) -> i64 {
+        let d = d.unwrap_or_else(utils::today);
+        let elapsed =
+            (d - self.starts_on).num_days() + 1 - self.paused_days_between(&self.starts_on, &d);
+        elapsed.clamp(0, self.active_days_at(&d).max(0))
+    }
+
+    /// Get the amount accrued by the transaction at date,
+    /// rounded to the currency decimals
+    ///
+    /// None will use today as a date.
+    /// It is the per diem times the days elapsed, after
+    /// the end date it is the total amount
+    pub fn accrued_to(&self, d: Option<NaiveDate>) -> BigDecimal {
+        let d = d.unwrap_or_else(utils::today);
+        let elapsed = self.days_elapsed(Some(d));
+        if elapsed >= self.active_days_at(&d) {
+            return self.round_amount(&self.get_amount_total_at(&d));
+        }
+        self.round_amount(&(self.per_diem_raw_at(&d) * BigDecimal::from_i64(elapsed).unwrap()))
+    }
+
     /// Returns the end date (always computed)
+    ///
+    /// The end date is the last day the transaction is active, so
+    /// the active window lasts exactly [`TxRecord::get_duration_days`] days.
+    ///
+    /// For an indefinite lifetime it is the end of the first repeat,
+    /// see [`TxRecord::get_ends_on_at`] for the end of the repeat
+    /// covering a date. The total amount, the per diem and the occurrences
+    /// follow the same window, while the transaction stays active on every
+    /// day after the end date too (see [`TxRecord::is_active_on`])
     pub fn get_ends_on(&self) -> NaiveDate {
-        self.starts_on + Duration::days(self.lifetime.get_days_since(&self.starts_on) - 1)
+        self.get_ends_on_at(&self.starts_on)
+    }
+    /// Returns the end date at a date
+    ///
+    /// For an indefinite lifetime it is the end of the repeat covering the date,
+    /// or of the first repeat for a date before the start, otherwise it is
+    /// the same as [`TxRecord::get_ends_on`]
+    pub fn get_ends_on_at(&self, d: &NaiveDate) -> NaiveDate {
+        self.starts_on + Duration::days(self.get_duration_days_at(d) - 1)
+    }
+
+    /// Returns the occurrences of the transaction, that is the start date
+    /// and the amount of each repeat of the lifetime
+    ///
+    /// For example `Rent 1000€ 1m3x 310121` occurs on the 31st of January,
+    /// the 28th of February and the 31st of March, 1000€ each time.
+    /// For an indefinite lifetime only the first repeat occurs,
+    /// see [`TxRecord::occurrences_at`]
+    pub fn occurrences(&self) -> impl Iterator<Item = (NaiveDate, BigDecimal)> + '_ {
+        self.occurrences_at(&self.starts_on)
+    }
+    /// Returns the occurrences of the transaction at a date
+    ///
+    /// For an indefinite lifetime these are the repeats up to
+    /// the one of the date, otherwise it is the same as [`TxRecord::occurrences`]
+    pub fn occurrences_at(
+        &self,
+        d: &NaiveDate,
+    ) -> impl Iterator<Item = (NaiveDate, BigDecimal)> + '_ {
+        let lifetime = self.lifetime_at(d);
+        (0..lifetime.get_repeats()).map(move |n| {
+            let offset = lifetime.with_times(n).get_days_since(&self.starts_on);
+            (self.starts_on + Duration::days(offset), self.get_amount())
+        })
+    }
+
+    /// Returns the number of days the transaction is active in a date range
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The first date of the range
+    /// * `to` - The last date of the range (included)
+    ///
+    /// The days from the start to the end date of the transaction are
+    /// clamped to the range and counted with both ends included,
+    /// the paused days are not counted (see [`TxRecord::is_active_on`]).
+    /// An indefinite transaction is active until the end of the range.
+    /// With no overlap, or an empty range, the result is zero
+    pub fn days_active_in_range(&self, from: &NaiveDate, to: &NaiveDate) -> i64 {
+        let start = max(self.starts_on, *from);
+        let end = match self.is_indefinite() {
+            true => *to,
+            false => min(self.get_ends_on(), *to),
+        };
+        let days = (end - start).num_days() + 1 - self.paused_days_between(&start, &end);
+        days.max(0)
+    }
+
+    /// Returns the cost accrued by the transaction in a date range
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The first date of the range
+    /// * `to` - The last date of the range (included)
+    ///
+    /// The cost is the raw per diem times the number of days the
+    /// transaction is active within the range (see [`TxRecord::days_active_in_range`]),
+    /// rounded to the currency decimals.
+    /// A transaction partially inside the range only accrues the days that
+    /// overlap with it, a transaction outside the range costs zero.
+    ///
+    pub fn cost_over_range(&self, from: &NaiveDate, to: &NaiveDate) -> BigDecimal {
+        let days = self.days_active_in_range(from, to);
+        if days == 0 {
+            return BigDecimal::zero().with_scale(self.get_scale());
+        }
+        self.round_amount(&(self.per_diem_raw_at(to) * BigDecimal::from_i64(days).unwrap()))
     }
 
     pub fn is_active_on(&self, target: &NaiveDate) -> bool {
-        self.starts_on <= *target && *target <= self.get_ends_on()
+        self.is_active_on_with(target, true)
+    }
+
+    /// Get the fraction of the day the transaction is active at a moment
+    ///
+    /// The fractional convention is:
+    /// * on the start day the fraction is the part of the day elapsed at `at`
+    /// * on the end day the fraction is the part of the day remaining after `at`
+    /// * for a single day transaction the smaller of the two
+    /// * 1 on the days in between and 0 outside the transaction
+    ///
+    /// the fractions are computed with a precision of one second
+    pub fn active_fraction_at(&self, at: &NaiveDateTime) -> BigDecimal {
+        let d = at.date();
+        if !self.is_active_on(&d) {
+            return BigDecimal::zero();
+        }
+        let day = BigDecimal::from_i64(86_400).unwrap();
+        let elapsed = BigDecimal::from_u32(at.num_seconds_from_midnight()).unwrap() / &day;
+        let remaining = BigDecimal::from_i64(1).unwrap() - &elapsed;
+        let is_end = !self.is_indefinite() && d == self.get_ends_on();
+        match (d == self.starts_on, is_end) {
+            (true, true) => min(elapsed, remaining),
+            (true, false) => elapsed,
+            (false, true) => remaining,
+            (false, false) => BigDecimal::from_i64(1).unwrap(),
+        }
+    }
+
+    /// Tells if the transaction is active on a date
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The date to check
+    /// * `end_inclusive` - Whether the transaction is still active on its end date
+    ///
+    /// With `end_inclusive` set to false the transaction is considered
+    /// expired on its end date, so it is active one day less
+    /// (a single day transaction is never active).
+    /// The transaction is not active on the paused days.
+    ///
+    pub fn is_active_on_with(&self, target: &NaiveDate, end_inclusive: bool) -> bool {
+        let in_range = match end_inclusive {
+            _ if self.is_indefinite() => self.starts_on <= *target,
+            true => self.starts_on <= *target && *target <= self.get_ends_on(),
+            false => self.starts_on <= *target && *target < self.get_ends_on(),
+        };
+        in_range && !self.is_paused_on(target)
     }
 
     /// Serialize the record to its string format
+    ///
+    /// The record is always rendered from its fields in a canonical
+    /// form, the source spec is ignored, so records that are the same
+    /// serialize the same regardless of how they were entered
     pub fn to_string_record(&self) -> String {
-        match &self.src {
-            Some(s) => {
-                format!(
-                    "{}::{}::{}\n",
-                    self.get_recorded_at_rfc3339(),
-                    self.get_starts_on(),
-                    s
-                )
-            }
-            None => format!(
-                "{}::{}::{} {}€ {} {}\n",
-                self.get_recorded_at_rfc3339(),
-                self.get_starts_on(),
-                self.get_name(),
+        format!(
+            "{}::{}::{}\n",
+            self.get_recorded_at_rfc3339(),
+            self.get_starts_on(),
+            self.render_spec(false)
+        )
+    }
+    /// Render the record as a spec string that can be parsed back
+    ///
+    /// The spec contains the name, the amount with its currency,
+    /// the lifetime, the start date (only if it is not today),
+    /// the category, the tags, the metadata, the pauses and the note as a trailing comment,
+    /// eg. `Netflix 7.99€ 1m12x 010321 #movies`
+    pub fn to_spec_string(&self) -> String {
+        self.render_spec(self.get_starts_on() != utils::today())
+    }
+    /// Render the spec of the record, with or without the start date
+    fn render_spec(&self, with_date: bool) -> String {
+        let mut spec = vec![
+            self.get_name().to_string(),
+            format!(
+                "{}{}",
                 self.get_amount(),
-                self.get_lifetime(),
-                self.get_tags()
-                    .iter()
-                    .map(|t| format!("#{}", t))
-                    .collect::<Vec<String>>()
-                    .join(" ")
+                currency_symbol(self.get_currency())
             ),
+            self.get_lifetime().to_string(),
+        ];
+        if with_date {
+            spec.push(self.get_starts_on().format("%d%m%y").to_string());
         }
+        if let Some(c) = self.get_category() {
+            spec.push(format!("::{}", c));
+        }
+        spec.extend(self.get_tags().iter().map(|t| format_hashtag(t)));
+        spec.extend(
+            self.get_metadata()
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v)),
+        );
+        spec.extend(self.pauses_spec());
+        if let Some(n) = self.get_note() {
+            spec.push(format!("// {}", n));
+        }
+        spec.join(" ")
     }
     // Deserialize the record from
     pub fn from_string_record(s: &str) -> Result<TxRecord> {
         let abc = s.trim().splitn(3, "::").collect::<Vec<&str>>();
-        let mut tx = Self::from_str(abc[2])?;
-        tx.starts_on = NaiveDate::from_str(abc[1])?;
+        if abc.len() < 3 {
+            return Err(CostOfLifeError::GenericError(format!(
+                "invalid record: {}",
+                s
+            )));
+        }
+        // the spec is read as of the start date, that may not be in the spec
+        let starts_on = NaiveDate::from_str(abc[1])?;
+        let mut tx = Self::from_str_on(abc[2], &starts_on)?;
+        tx.starts_on = starts_on;
+        tx.clear_per_diem();
         tx.recorded_at = DateTime::parse_from_rfc3339(abc[0])?;
         Ok(tx)
     }
@@ -423,27 +1695,111 @@ impl TxRecord {
         recorded_at: DateTime<FixedOffset>,
         src: Option<&str>,
     ) -> Result<TxRecord> {
-        let tx = TxRecord {
+        let tx = TxRecord::build(name, tags, amount, starts_on, lifetime, recorded_at, src)?;
+        tx.validate()?;
+        // all good
+        Ok(tx)
+    }
+
+    /// Builds a TxRecord in the default currency without validating it,
+    /// for the constructors that change the amount or the currency
+    fn build(
+        name: &str,
+        tags: Vec<&str>,
+        amount: &str,
+        starts_on: NaiveDate,
+        lifetime: Lifetime,
+        recorded_at: DateTime<FixedOffset>,
+        src: Option<&str>,
+    ) -> Result<TxRecord> {
+        Ok(TxRecord {
             name: String::from(name.trim()),
             tags: tags
                 .iter()
-                .map(|v| (slugify(v), String::from(*v)))
+                .map(|v| (tag_slug(v), v.nfc().collect::<String>()))
                 .collect(),
             amount: parse_amount(amount)
                 .ok_or_else(|| CostOfLifeError::InvalidAmount("Invalid amount".to_string()))?,
+            meta: HashMap::new(),
+            currency: DEFAULT_CURRENCY.to_string(),
+            category: None,
+            note: None,
+            pauses: Vec::new(),
             lifetime,
             recorded_at,
             starts_on,
-            src: Some(src).map(|s| String::from(s.unwrap())),
-        };
-        // validate the amount
-        if tx.get_amount() <= BigDecimal::zero() {
+            src: src.map(String::from),
+            per_diem_cache: OnceCell::new(),
+        })
+    }
+
+    /// Builds a TxRecord with an amount in minor units (eg. cents)
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - A string slice that holds the name of the transaction
+    /// * `cents` - The amount in the minor unit of the currency
+    /// * `currency` - The currency, as a symbol or an ISO 4217 code
+    /// * `starts_on` - The date of the start of the transaction
+    /// * `lifetime` - The lifetime of transaction
+    ///
+    /// The amount is built exactly using the decimals of the currency,
+    /// see [`parse_amount_cents`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use costoflife::{self, TxRecord, Lifetime};
+    ///
+    /// let tx = TxRecord::from_cents(
+    ///     "Netflix",
+    ///     799,
+    ///     "€",
+    ///     costoflife::date(01, 01, 2021),
+    ///     Lifetime::SingleDay,
+    /// ).unwrap();
+    /// assert_eq!(tx.get_amount().to_string(), "7.99");
+    /// ```
+    pub fn from_cents(
+        name: &str,
+        cents: i64,
+        currency: &str,
+        starts_on: NaiveDate,
+        lifetime: Lifetime,
+    ) -> Result<TxRecord> {
+        let currency = currency_code(currency);
+        let mut tx = TxRecord::build(
+            name,
+            Vec::new(),
+            "1",
+            starts_on,
+            lifetime,
+            utils::now_local(),
+            None,
+        )?;
+        tx.amount = parse_amount_cents(cents, currency);
+        tx.currency = currency.to_string();
+        tx.validate()?;
+        Ok(tx)
+    }
+
+    /// Check that the amount is not zero and the total is within the limits,
+    /// negative amounts (eg. refunds or income) are allowed
+    fn validate(&self) -> Result<()> {
+        if self.get_amount().is_zero() {
             return Err(CostOfLifeError::InvalidAmount(
-                format! {"amount should be a positive number: {}", amount},
+                format! {"amount should not be zero: {}", self.get_amount()},
             ));
         }
-        // all good
-        Ok(tx)
+        if self.get_amount_total().abs() > BigDecimal::from_i64(MAX_AMOUNT_TOTAL).unwrap() {
+            return Err(CostOfLifeError::InvalidAmount(format!(
+                "the total amount should not exceed {}: {} x {}",
+                MAX_AMOUNT_TOTAL,
+                self.get_amount(),
+                self.get_lifetime().get_repeats()
+            )));
+        }
+        Ok(())
     }
 }
 
@@ -451,46 +1807,141 @@ impl FromStr for TxRecord {
     type Err = CostOfLifeError;
 
     fn from_str(s: &str) -> Result<Self> {
+        TxRecord::from_str_on(s, &utils::today())
+    }
+}
+
+impl TxRecord {
+    /// Parse a spec string as if today was a different date
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The spec string
+    /// * `on` - The date used as today, for the default and relative
+    ///   start dates and for the recording time
+    ///
+    pub fn from_str_on(s: &str, on: &NaiveDate) -> Result<TxRecord> {
+        TxRecord::from_str_in(s, on, DEFAULT_CURRENCY)
+    }
+
+    /// Parse a spec string as if today was a different date,
+    /// with a different default currency
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The spec string
+    /// * `on` - The date used as today, see [`TxRecord::from_str_on`]
+    /// * `currency` - The currency, symbol or ISO 4217 code, of the amount
+    ///   with the generic currency sign (eg. `10¤`)
+    ///
+    pub fn from_str_in(s: &str, on: &NaiveDate, currency: &str) -> Result<TxRecord> {
         // make an empty record
         let mut name: Vec<&str> = Vec::new();
-        let mut amount = "0";
-        let mut lifetime = Lifetime::SingleDay;
+        let mut amount: Option<(&str, &str)> = None;
+        let mut lifetime: Option<Lifetime> = None;
         let mut tags: Vec<&str> = Vec::new();
-        let mut starts_on = utils::today();
-        // search for the stuff we need
-        for t in s.split_whitespace() {
-            if RE_CURRENCY.is_match(t) {
-                // read the currency
-                if let Some(a) = extract_amount(t) {
-                    amount = a
+        let mut meta: HashMap<String, String> = HashMap::new();
+        let mut starts_on: Option<NaiveDate> = None;
+        let mut ends_on: Option<NaiveDate> = None;
+        let mut category: Option<&str> = None;
+        let mut share: Option<i64> = None;
+        let mut pauses: Vec<(NaiveDate, NaiveDate)> = Vec::new();
+        // the trailing comment is kept as a note
+        let (spec, note) = split_comment(s);
+        let tokens = tokenize(spec).collect::<Vec<&str>>();
+        // a lifetime in long form is read only if there is no other lifetime,
+        // otherwise its tokens are words of the name (eg. `Top 10 Days 30€ 1m`)
+        let long_lifetime = match tokens.iter().any(|t| is_full_match(&RE_LIFETIME, t)) {
+            true => None,
+            false => find_long_lifetime(&tokens),
+        };
+        // each token has a single role, and most roles can be set only once
+        for (i, &t) in tokens.iter().enumerate() {
+            let token = match &long_lifetime {
+                Some((start, _, l)) if i == *start => Token::Lifetime(l.parse::<Lifetime>()?),
+                Some((start, n, _)) if i > *start && i < start + n => continue,
+                _ => Token::classify(t, on)?,
+            };
+            match token {
+                Token::Amount(a, c) => set_once(&mut amount, (a, c), || {
+                    CostOfLifeError::InvalidAmount(format!("the amount is set twice: {}", t))
+                })?,
+                Token::Share(n) => set_once(&mut share, n, || {
+                    CostOfLifeError::GenericError(format!("the share is set twice: {}", t))
+                })?,
+                Token::Tag(tag) => tags.push(tag),
+                // the last category wins
+                Token::Category(c) => category = Some(c),
+                Token::Pauses(p) => pauses.extend(p),
+                Token::Meta(k, v) => {
+                    meta.insert(k.to_string(), v.to_string());
                 }
-            } else if RE_HASHTAG.is_match(t) {
-                // add tags
-                if let Some(x) = extract_hashtag(t) {
-                    tags.push(x);
+                Token::StartDate(d) => set_once(&mut starts_on, d, || {
+                    CostOfLifeError::InvalidDateFormat(format!(
+                        "the start date is set twice: {}",
+                        t
+                    ))
+                })?,
+                Token::Lifetime(l) => set_once(&mut lifetime, l, || {
+                    CostOfLifeError::InvalidLifetimeFormat(format!(
+                        "the lifetime is set twice: {}",
+                        t
+                    ))
+                })?,
+                Token::DateRange(start, end) => {
+                    if let Some(start) = start {
+                        set_once(&mut starts_on, start, || {
+                            CostOfLifeError::InvalidDateFormat(format!(
+                                "the start date is set twice: {}",
+                                t
+                            ))
+                        })?;
+                    }
+                    set_once(&mut ends_on, end, || {
+                        CostOfLifeError::InvalidDateFormat(format!(
+                            "the end date is set twice: {}",
+                            t
+                        ))
+                    })?;
                 }
-            } else if RE_LIFETIME.is_match(t) {
-                // add duration
-                lifetime = t.parse::<Lifetime>()?;
-            } else if RE_DATE.is_match(t) {
-                // start date
-                starts_on = extract_date(t)
-                    .ok_or_else(|| CostOfLifeError::GenericError(String::from(":")))?;
-            } else {
                 // catch all for the name
-                name.push(t)
+                Token::Word(w) => name.push(w),
             }
         }
-        // build the tx record
-        TxRecord::from(
+        let (amount, currency) = match amount {
+            Some((a, GENERIC_CURRENCY)) => (a, currency),
+            Some((a, c)) => (a, c),
+            None => ("0", currency),
+        };
+        let currency = currency_code(currency);
+        let starts_on = starts_on.unwrap_or(*on);
+        let mut lifetime = lifetime.unwrap_or(Lifetime::SingleDay);
+        // repeat the lifetime until the end date
+        if let Some(end) = ends_on {
+            lifetime = Lifetime::from_start_end_period(&starts_on, &end, &lifetime)?;
+        }
+        // build the tx record, it is validated in its currency
+        let mut tx = TxRecord::build(
             &name.join(" "),
             tags,
             amount,
             starts_on,
             lifetime,
-            utils::now_local(),
+            utils::now_local_on(on),
             Some(s),
-        )
+        )?;
+        tx.currency = currency.to_string();
+        tx.validate()?;
+        tx.meta = meta;
+        tx.category = category.map(String::from);
+        tx.note = note.map(String::from);
+        for (from, to) in pauses {
+            tx.insert_pause(from, to)?;
+        }
+        match share {
+            Some(n) => tx.with_share(&(BigDecimal::one() / BigDecimal::from_i64(n).unwrap())),
+            None => Ok(tx),
+        }
     }
 }
 
@@ -505,8 +1956,12 @@ impl PartialEq for TxRecord {
         self.name.eq(&other.name)
             && self.tags.eq(&other.tags)
             && self.amount.eq(&other.amount)
+            && self.currency.eq(&other.currency)
+            && self.meta.eq(&other.meta)
+            && self.category.eq(&other.category)
             && self.starts_on.eq(&other.starts_on)
             && self.lifetime.eq(&other.lifetime)
+            && self.pauses.eq(&other.pauses)
     }
 }
 
@@ -516,12 +1971,155 @@ pub fn cost_of_life<'a, I>(txs: I, on: &NaiveDate) -> BigDecimal
 where
     I: Iterator<Item = &'a TxRecord>,
 {
-    txs.filter(|tx| tx.is_active_on(on)) // is still an active expense
-        .map(|tx| tx.per_diem_raw())
+    cost_of_life_with(txs, on, true)
+}
+
+/// Compute the cost of life for a set of transactions
+/// choosing if the transactions are active on their end date
+///
+/// With `end_inclusive` set to false the transactions ending on
+/// the date are excluded, so the total on the last day of a
+/// transaction is lower by its per diem (see [`TxRecord::is_active_on_with`])
+///
+pub fn cost_of_life_with<'a, I>(txs: I, on: &NaiveDate, end_inclusive: bool) -> BigDecimal
+where
+    I: Iterator<Item = &'a TxRecord>,
+{
+    txs.filter(|tx| tx.is_active_on_with(on, end_inclusive)) // is still an active expense
+        .map(|tx| tx.per_diem_raw_at(on))
         .sum::<BigDecimal>() // sum all the amount
         .with_scale(SCALE) // apply the scale
 }
 
+/// Compute the cost of life for a set of transactions over a period,
+/// that is the cost of life times the days of the period
+///
+/// The multiplication is done before rounding, so the result
+/// matches the sum of [`TxRecord::per_period`] up to the rounding
+///
+pub fn cost_of_life_per<'a, I>(txs: I, on: &NaiveDate, period: &Period) -> BigDecimal
+where
+    I: Iterator<Item = &'a TxRecord>,
+{
+    (txs.filter(|tx| tx.is_active_on(on))
+        .map(|tx| tx.per_diem_raw_at(on))
+        .sum::<BigDecimal>()
+        * period.days())
+    .with_scale(SCALE)
+}
+
+/// Compute the committed cost of life for a set of transactions
+///
+/// # Arguments
+///
+/// * `txs` - The transactions
+/// * `from` - The first day of the period
+/// * `horizon_days` - The number of days after `from` the period lasts
+///
+/// Unlike [`cost_of_life`], that only counts the transactions active on a
+/// single day, this sums the per diem of all the transactions that are active
+/// at any point in `[from, from + horizon_days]`, including the ones that
+/// have not started yet or that end within the period. It answers the question
+/// "how much am I committing to in the next days". A negative horizon is
+/// the same as a zero one.
+///
+pub fn committed_cost<'a, I>(txs: I, from: &NaiveDate, horizon_days: i64) -> BigDecimal
+where
+    I: Iterator<Item = &'a TxRecord>,
+{
+    let to = *from + Duration::days(max(horizon_days, 0));
+    txs.filter(|tx| tx.get_starts_on() <= to && tx.get_ends_on_at(from) >= *from)
+        .map(|tx| tx.per_diem_raw_at(from))
+        .sum::<BigDecimal>()
+        .with_scale(SCALE)
+}
+
+/// Compute the cost of life for a set of transactions at a moment
+///
+/// # Arguments
+///
+/// * `txs` - The transactions
+/// * `at` - The moment to compute the cost of life for
+/// * `include_partial_day` - Whether to weight the per diem on the start and end days
+///
+/// With `include_partial_day` set to false this is the same as [`cost_of_life`]
+/// on the date of `at`, otherwise on the start and end days of a transaction
+/// only a fraction of its per diem is counted (see [`TxRecord::active_fraction_at`])
+///
+pub fn cost_of_life_at<'a, I>(txs: I, at: &NaiveDateTime, include_partial_day: bool) -> BigDecimal
+where
+    I: Iterator<Item = &'a TxRecord>,
+{
+    if !include_partial_day {
+        return cost_of_life(txs, &at.date());
+    }
+    txs.map(|tx| tx.per_diem_raw_at(&at.date()) * tx.active_fraction_at(at))
+        .sum::<BigDecimal>()
+        .with_scale(SCALE)
+}
+
+/// Compute the contribution to set aside each period to reach
+/// a target amount by a date, that is a sinking fund
+///
+/// # Arguments
+///
+/// * `target` - The amount to reach
+/// * `from` - The date of the first contribution
+/// * `until` - The date the target is needed by
+/// * `period` - The period between the contributions, its repetitions are ignored
+///
+/// A contribution is set aside at the start of each period, from `from` until
+/// the day before `until`, so a monthly schedule over a year has 12 contributions.
+/// The contribution is rounded up to 2 decimals, so the contributions reach
+/// the target. Returns an error if the target is not positive or `until` is not after `from`
+///
+/// # Examples
+///
+/// ```
+/// use costoflife::{self, Lifetime};
+///
+/// let monthly = costoflife::sinking_fund(
+///     costoflife::parse_amount("1200").unwrap(),
+///     costoflife::date(1, 1, 2022),
+///     costoflife::date(1, 1, 2023),
+///     Lifetime::Month {
+///         amount: 1,
+///         times: 1,
+///     },
+/// ).unwrap();
+/// assert_eq!(monthly.to_string(), "100.00");
+/// ```
+pub fn sinking_fund(
+    target: BigDecimal,
+    from: NaiveDate,
+    until: NaiveDate,
+    period: Lifetime,
+) -> Result<BigDecimal> {
+    if target <= BigDecimal::zero() {
+        return Err(CostOfLifeError::InvalidAmount(format!(
+            "the target should be a positive number: {}",
+            target
+        )));
+    }
+    if until <= from {
+        return Err(CostOfLifeError::InvalidDateFormat(format!(
+            "the date {} is not after {}",
+            until, from
+        )));
+    }
+    // the contributions up to the day before the target date
+    let contributions = BigDecimal::from_i64(
+        Lifetime::from_start_end_period(&from, &(until - Duration::days(1)), &period)?
+            .get_repeats(),
+    )
+    .unwrap();
+    let amount = (&target / &contributions).with_scale(SCALE);
+    match &amount * &contributions < target {
+        true => Ok(amount + BigDecimal::new(1.into(), SCALE)),
+        false => Ok(amount),
+    }
+}
+
 #[cfg(test)]
 pub mod wasm_tests {
     use wasm_bindgen_test::*;
@@ -538,6 +2136,26 @@ pub mod wasm_tests {
     fn test_per_diem() {
         assert_eq!(super::costoflife_per_diem("20€ rent"), 20.0);
     }
+
+    #[test]
+    #[wasm_bindgen_test]
+    fn test_cost_by_tag() {
+        let data = "2021-01-01T10:00:00+01:00::2021-01-01::Rent 310€ 1m #home #fixed\n\
+                    2021-01-01T10:00:00+01:00::2021-01-01::Phone 31€ 1m #fixed\n";
+        let got = super::cost_by_tag(data, "150121");
+        assert_eq!(got.len(), 2);
+        assert_eq!(got.get("home"), Some(&10.0));
+        assert_eq!(got.get("fixed"), Some(&11.0));
+        assert!(super::cost_by_tag(data, "150221").is_empty());
+        assert!(super::cost_by_tag(data, "whatever").is_empty());
+        assert!(super::cost_by_tag("", "150121").is_empty());
+        // as json
+        assert_eq!(
+            super::costoflife_cost_by_tag(data, "150121"),
+            r#"{"fixed":11.0,"home":10.0}"#
+        );
+        assert_eq!(super::costoflife_cost_by_tag(data, "whatever"), "{}");
+    }
 }
 
 #[cfg(test)]
@@ -634,8 +2252,8 @@ mod tests {
                     28,                                                  // duration days
                     vec![("internet", true)],                            // tags
                     (date(12, 5, 2021), true),                           // is active
-                    parse_amount("1.42").unwrap(),                       // per diem
-                    (Some(date(5, 5, 2021)), 0.5185185185185185_f32), // progress
+                    parse_amount("1.43").unwrap(),                       // per diem
+                    (Some(date(5, 5, 2021)), 0.518_518_5_f32), // progress
                 ),
             ),
             (
@@ -655,7 +2273,7 @@ mod tests {
                 (
                     Ok(()),
                     "Car",
-                    date(01, 01, 2010),
+                    date(1, 1, 2010),
                     (date(31, 12, 2029)),
                     7305,
                     vec![
@@ -666,8 +2284,8 @@ mod tests {
                         ("lifestyle", true),
                     ],
                     (date(1, 1, 2030), false),
-                    parse_amount("13.68").unwrap(),
-                    (Some(date(1, 10, 2020)), 0.537513691128149_f32),
+                    parse_amount("13.69").unwrap(),
+                    (Some(date(1, 10, 2020)), 0.537_513_7_f32),
                 ),
             ),
             (
@@ -723,8 +2341,8 @@ mod tests {
                     28,                                                  // duration days
                     vec![("internet", true)],                            // tags
                     (date(12, 5, 2021), true),                           // is active
-                    parse_amount("1.42").unwrap(),                       // per diem
-                    (Some(date(5, 5, 2021)), 0.5185185185185185_f32), // progress
+                    parse_amount("1.43").unwrap(),                       // per diem
+                    (Some(date(5, 5, 2021)), 0.518_518_5_f32), // progress
                 ),
             ),
             (
@@ -738,8 +2356,8 @@ mod tests {
                     28,                                                  // duration days
                     vec![("internet", true)],                            // tags
                     (date(12, 5, 2021), true),                           // is active
-                    parse_amount("1.42").unwrap(),                       // per diem
-                    (Some(date(5, 5, 2021)), 0.5185185185185185_f32), // progress
+                    parse_amount("1.43").unwrap(),                       // per diem
+                    (Some(date(5, 5, 2021)), 0.518_518_5_f32), // progress
                 ),
             ),
             (
@@ -753,8 +2371,8 @@ mod tests {
                     28,                                                  // duration days
                     vec![("internet", true)],                            // tags
                     (date(12, 5, 2021), true),                           // is active
-                    parse_amount("1.42").unwrap(),                       // per diem
-                    (Some(date(5, 5, 2021)), 0.5185185185185185_f32), // progress
+                    parse_amount("1.43").unwrap(),                       // per diem
+                    (Some(date(5, 5, 2021)), 0.518_518_5_f32), // progress
                 ),
             ),
         ];
@@ -898,6 +2516,27 @@ mod tests {
                     times: 12,
                 },
             ),
+            (
+                ("1m", date(1, 12, 2021), 31, "1m1x"),
+                Lifetime::Month {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                ("1m", date(31, 1, 2021), 28, "1m1x"),
+                Lifetime::Month {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                ("1y", date(29, 2, 2020), 365, "1y1x"),
+                Lifetime::Year {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
             (
                 ("", today(), 1, "1d1x"),
                 Lifetime::Day {
@@ -924,19 +2563,1858 @@ mod tests {
             // to string
             assert_eq!(lifetime_exp.to_string(), *to_str);
         }
-    }
-
-    #[test]
-    fn test_extract() {
-        // extract not matching date
-        // this cannot happen but anyway
-        let r = extract_date("invalid date");
-        assert_eq!(r.unwrap(), today());
+        // uppercase and long units
+        let tests = vec![
+            (
+                "1M",
+                Lifetime::Month {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                "2Y",
+                Lifetime::Year {
+                    amount: 2,
+                    times: 1,
+                },
+            ),
+            (
+                "3W2x",
+                Lifetime::Week {
+                    amount: 3,
+                    times: 2,
+                },
+            ),
+            (
+                "1D",
+                Lifetime::Day {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                "3months",
+                Lifetime::Month {
+                    amount: 3,
+                    times: 1,
+                },
+            ),
+            (
+                "1month12x",
+                Lifetime::Month {
+                    amount: 1,
+                    times: 12,
+                },
+            ),
+            (
+                "2Years",
+                Lifetime::Year {
+                    amount: 2,
+                    times: 1,
+                },
+            ),
+            (
+                "1week",
+                Lifetime::Week {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                "10days",
+                Lifetime::Day {
+                    amount: 10,
+                    times: 1,
+                },
+            ),
+        ];
+        for (s, exp) in tests {
+            assert_eq!(s.parse::<Lifetime>().unwrap(), exp);
+            assert_eq!(s.parse::<Lifetime>().unwrap().to_string(), exp.to_string());
+        }
+        // in a spec
+        let tests = vec![
+            ("Rent 1000€ 1M", "1m1x"),
+            ("Car 5000€ 2Y", "2y1x"),
+            ("Gym 90€ 3 months", "3m1x"),
+            ("Gym 30€ 1 Month 12x", "1m12x"),
+            ("Gym 30€ 1month12x", "1m12x"),
+            ("Hotel 300€ 3 days", "3d1x"),
+            ("Rent 1000€ 1m12x", "1m12x"),
+        ];
+        for (s, exp) in tests {
+            let tx = TxRecord::from_str(s).unwrap();
+            assert_eq!(tx.get_lifetime().to_string(), exp);
+            assert_eq!(tx.get_name(), s.split(' ').next().unwrap());
+        }
+        // the long form is made of standalone tokens
+        let tx = TxRecord::from_str("Trip #\"3 days trip\" 300€ 1w 010121").unwrap();
+        assert_eq!(tx.get_tags(), vec!["3 days trip"]);
+        assert_eq!(tx.get_lifetime().to_string(), "1w1x");
+        // and it is a lifetime only if there is no other one
+        let tx = TxRecord::from_str("Top 10 Days 30€ 1m 010121").unwrap();
+        assert_eq!(tx.get_name(), "Top 10 Days");
+        assert_eq!(tx.get_lifetime().to_string(), "1m1x");
+    }
+
+    #[test]
+    fn test_progress_zero_length() {
+        // a single day transaction
+        let tx = TxRecord::new("Coffee", "2").unwrap();
+        assert_eq!(tx.get_starts_on(), tx.get_ends_on());
+        assert_eq!(tx.get_progress(Some(today() - Duration::days(1))), 0.0);
+        assert_eq!(tx.get_progress(Some(today())), 0.0);
+        assert_eq!(tx.get_progress(Some(today() + Duration::days(1))), 1.0);
+        // a degenerate zero length lifetime
+        let tx = TxRecord::from(
+            "Nothing",
+            vec![],
+            "10",
+            date(1, 1, 2021),
+            Lifetime::Day {
+                amount: 0,
+                times: 1,
+            },
+            now_local(),
+            None,
+        )
+        .unwrap();
+        for d in 0..3 {
+            let p = tx.get_progress(Some(date(1, 1, 2021) + Duration::days(d - 1)));
+            assert!(!p.is_nan());
+            assert!((0.0..=1.0).contains(&p));
+        }
+    }
+
+    #[test]
+    fn test_active_end_exclusive() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        let (start, end) = (date(1, 1, 2021), date(31, 1, 2021));
+        assert_eq!(tx.get_ends_on(), end);
+        // before the start
+        assert!(!tx.is_active_on_with(&date(31, 12, 2020), true));
+        assert!(!tx.is_active_on_with(&date(31, 12, 2020), false));
+        // on the start
+        assert!(tx.is_active_on_with(&start, true));
+        assert!(tx.is_active_on_with(&start, false));
+        // on the end
+        assert!(tx.is_active_on(&end));
+        assert!(tx.is_active_on_with(&end, true));
+        assert!(!tx.is_active_on_with(&end, false));
+        // single day
+        let tx = TxRecord::from_str("Coffee 2€ 010121").unwrap();
+        assert!(tx.is_active_on_with(&start, true));
+        assert!(!tx.is_active_on_with(&start, false));
+        // the cost of life on the boundary
+        let txs = [
+            TxRecord::from_str("Rent 310€ 1m 010121").unwrap(),
+            TxRecord::from_str("Phone 28€ 1m 010221").unwrap(),
+        ];
+        let got = cost_of_life_with(txs.iter(), &end, true);
+        assert_eq!(got, parse_amount("10").unwrap());
+        let got = cost_of_life_with(txs.iter(), &end, false);
+        assert_eq!(got, BigDecimal::zero());
+        let got = cost_of_life_with(txs.iter(), &date(1, 2, 2021), false);
+        assert_eq!(got, parse_amount("1").unwrap());
+        assert_eq!(cost_of_life(txs.iter(), &end), parse_amount("10").unwrap());
+    }
+
+    #[test]
+    fn test_date_at_progress() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        assert_eq!(tx.date_at_progress(0.0), Some(date(1, 1, 2021)));
+        assert_eq!(tx.date_at_progress(0.5), Some(date(16, 1, 2021)));
+        assert_eq!(tx.date_at_progress(1.0), Some(date(31, 1, 2021)));
+        assert_eq!(tx.date_at_progress(1.0), Some(tx.get_ends_on()));
+        // it is the inverse of get_progress
+        for f in &[0.0, 0.5, 1.0] {
+            let d = tx.date_at_progress(*f);
+            assert_eq!(tx.get_progress(d) as f64, *f);
+        }
+        // out of range
+        assert_eq!(tx.date_at_progress(-0.1), None);
+        assert_eq!(tx.date_at_progress(1.1), None);
+        assert_eq!(tx.date_at_progress(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_accrued() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        // before the start
+        assert_eq!(tx.days_elapsed(Some(date(31, 12, 2020))), 0);
+        assert_eq!(tx.accrued_to(Some(date(31, 12, 2020))), BigDecimal::zero());
+        // mid life
+        assert_eq!(tx.days_elapsed(Some(date(1, 1, 2021))), 1);
+        assert_eq!(
+            tx.accrued_to(Some(date(1, 1, 2021))),
+            parse_amount("10").unwrap()
+        );
+        assert_eq!(tx.days_elapsed(Some(date(15, 1, 2021))), 15);
+        assert_eq!(
+            tx.accrued_to(Some(date(15, 1, 2021))),
+            parse_amount("150").unwrap()
+        );
+        // after the end
+        assert_eq!(tx.days_elapsed(Some(date(31, 1, 2021))), 31);
+        assert_eq!(
+            tx.accrued_to(Some(date(31, 1, 2021))),
+            parse_amount("310").unwrap()
+        );
+        assert_eq!(tx.days_elapsed(Some(date(1, 6, 2022))), 31);
+        assert_eq!(
+            tx.accrued_to(Some(date(1, 6, 2022))),
+            parse_amount("310").unwrap()
+        );
+        // the total is not affected by the per diem rounding
+        let tx = TxRecord::from_str("Phone 100€ 1m3x 010121").unwrap();
+        assert_eq!(
+            tx.accrued_to(Some(date(1, 1, 2022))),
+            parse_amount("300").unwrap()
+        );
+        assert_eq!(
+            tx.accrued_to(Some(date(1, 1, 2021))),
+            parse_amount("3.33").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_partial_day() {
+        let txs = [
+            TxRecord::from_str("Rent 310€ 1m 010121").unwrap(),
+            TxRecord::from_str("Phone 28€ 1m 010221").unwrap(),
+            TxRecord::from_str("Coffee 2€ 150121").unwrap(),
+        ];
+        let at = |d: u32, m: u32, h: u32| date(d, m, 2021).and_hms(h, 0, 0);
+        // whole days
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(1, 1, 6), false),
+            parse_amount("10").unwrap()
+        );
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(31, 1, 18), false),
+            parse_amount("10").unwrap()
+        );
+        // start day, a quarter of the day elapsed
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(1, 1, 6), true),
+            parse_amount("2.5").unwrap()
+        );
+        // end day, a quarter of the day remaining
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(31, 1, 18), true),
+            parse_amount("2.5").unwrap()
+        );
+        // start day of another transaction, three quarters of the day elapsed
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(1, 2, 18), true),
+            parse_amount("0.75").unwrap()
+        );
+        // in between
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(10, 1, 18), true),
+            parse_amount("10").unwrap()
+        );
+        // single day, the smaller of the two
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(15, 1, 6), true),
+            parse_amount("10.5").unwrap()
+        );
+        assert_eq!(
+            cost_of_life_at(txs.iter(), &at(15, 1, 12), true),
+            parse_amount("11").unwrap()
+        );
+        // midnight
+        assert_eq!(txs[0].active_fraction_at(&at(1, 1, 0)), BigDecimal::zero());
+        assert_eq!(
+            txs[0].active_fraction_at(&at(31, 1, 0)),
+            parse_amount("1").unwrap()
+        );
+        assert_eq!(txs[0].active_fraction_at(&at(1, 2, 0)), BigDecimal::zero());
+    }
+
+    #[test]
+    fn test_active_days() {
+        let tests = vec![
+            "Gym 100€ 100d 010121",
+            "Rent 1000€ 1m 010121",
+            "Rent 1000€ 1m12x 310121",
+            "Groceries 50€ 1w52x 290220",
+            "Insurance 300€ 1y 290220",
+            "Coffee 2€ 010121",
+        ];
+        for s in tests {
+            let tx = TxRecord::from_str(s).unwrap();
+            // count the distinct days the transaction is active
+            let mut d = tx.get_starts_on() - Duration::days(10);
+            let mut active = 0;
+            let mut accrued = BigDecimal::zero();
+            while d <= tx.get_ends_on() + Duration::days(10) {
+                if tx.is_active_on(&d) {
+                    active += 1;
+                    accrued += tx.per_diem_raw();
+                }
+                d += Duration::days(1);
+            }
+            assert_eq!(active, tx.get_duration_days(), "{}", s);
+            assert_eq!(
+                round_half_up(&accrued, SCALE),
+                tx.get_amount_total().with_scale(SCALE),
+                "{}",
+                s
+            );
+        }
+        let tx = TxRecord::from_str("Gym 100€ 100d 010121").unwrap();
+        assert_eq!(tx.get_duration_days(), 100);
+        assert_eq!(tx.get_ends_on(), date(10, 4, 2021));
+        assert_eq!(tx.per_diem(), parse_amount("1").unwrap());
+    }
+
+    #[test]
+    fn test_cost_over_range() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        // fully inside the range
+        let got = tx.cost_over_range(&date(1, 12, 2020), &date(31, 12, 2021));
+        assert_eq!(got, parse_amount("310").unwrap());
+        // partially inside the range
+        let got = tx.cost_over_range(&date(20, 12, 2020), &date(10, 1, 2021));
+        assert_eq!(got, parse_amount("100").unwrap());
+        let got = tx.cost_over_range(&date(31, 1, 2021), &date(10, 2, 2021));
+        assert_eq!(got, parse_amount("10").unwrap());
+        // range inside the tx
+        let got = tx.cost_over_range(&date(5, 1, 2021), &date(6, 1, 2021));
+        assert_eq!(got, parse_amount("20").unwrap());
+        // outside the range
+        let got = tx.cost_over_range(&date(1, 2, 2021), &date(10, 2, 2021));
+        assert_eq!(got, BigDecimal::zero());
+        // empty range
+        let got = tx.cost_over_range(&date(10, 1, 2021), &date(5, 1, 2021));
+        assert_eq!(got, BigDecimal::zero());
+    }
+
+    #[test]
+    fn test_days_active_in_range() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        let tests = vec![
+            // the range covers the tx
+            ((1, 12, 2020), (31, 12, 2021), 31),
+            ((1, 1, 2021), (31, 1, 2021), 31),
+            // the range overlaps the start or the end
+            ((20, 12, 2020), (10, 1, 2021), 10),
+            ((31, 1, 2021), (10, 2, 2021), 1),
+            ((20, 12, 2020), (1, 1, 2021), 1),
+            // the range is inside the tx
+            ((5, 1, 2021), (6, 1, 2021), 2),
+            ((15, 1, 2021), (15, 1, 2021), 1),
+            // no overlap
+            ((1, 2, 2021), (10, 2, 2021), 0),
+            ((1, 12, 2020), (31, 12, 2020), 0),
+            // empty range
+            ((10, 1, 2021), (5, 1, 2021), 0),
+        ];
+        for ((fd, fm, fy), (td, tm, ty), expected) in tests {
+            let (from, to) = (date(fd, fm, fy), date(td, tm, ty));
+            assert_eq!(
+                tx.days_active_in_range(&from, &to),
+                expected,
+                "{} {}",
+                from,
+                to
+            );
+            // the same as counting the active days one by one
+            let counted = from
+                .iter_days()
+                .take_while(|d| d <= &to)
+                .filter(|d| tx.is_active_on(d))
+                .count();
+            assert_eq!(counted as i64, expected, "{} {}", from, to);
+        }
+        // the paused days are not counted
+        let tx = TxRecord::from_str("Gym 300€ 30d 010121 paused=110121..200121").unwrap();
+        assert_eq!(
+            tx.days_active_in_range(&date(1, 1, 2021), &date(31, 1, 2021)),
+            20
+        );
+        assert_eq!(
+            tx.days_active_in_range(&date(5, 1, 2021), &date(15, 1, 2021)),
+            6
+        );
+        assert_eq!(
+            tx.days_active_in_range(&date(12, 1, 2021), &date(18, 1, 2021)),
+            0
+        );
+        // a single day
+        let tx = TxRecord::from_str("Coffee 2€ 010121").unwrap();
+        assert_eq!(
+            tx.days_active_in_range(&date(1, 1, 2021), &date(1, 1, 2021)),
+            1
+        );
+        assert_eq!(
+            tx.days_active_in_range(&date(2, 1, 2021), &date(9, 1, 2021)),
+            0
+        );
+        // until cancelled, active until the end of the range
+        let tx = TxRecord::from_str("Rent 1000€ 1m+ 010121").unwrap();
+        assert_eq!(
+            tx.days_active_in_range(&date(1, 1, 2021), &date(31, 12, 2021)),
+            365
+        );
+        assert_eq!(
+            tx.days_active_in_range(&date(1, 1, 2020), &date(31, 12, 2020)),
+            0
+        );
+    }
+
+    #[test]
+    fn test_per_diem_cache() {
+        let mut tx = TxRecord::from_str("Gym 300€ 30d 010121").unwrap();
+        assert!(tx.per_diem_cache.get().is_none());
+        assert_eq!(tx.per_diem_raw(), tx.compute_per_diem(&today()));
+        assert_eq!(
+            tx.per_diem_cache.get(),
+            Some(&tx.compute_per_diem(&today()))
+        );
+        // a cached copy
+        let copy = tx.clone();
+        assert_eq!(copy.per_diem(), parse_amount("10").unwrap());
+        // the changes clear the cache
+        tx.add_pause(date(11, 1, 2021), date(20, 1, 2021)).unwrap();
+        assert!(tx.per_diem_cache.get().is_none());
+        assert_eq!(tx.per_diem(), parse_amount("15").unwrap());
+        let shared = copy.with_share(&parse_amount("0.5").unwrap()).unwrap();
+        assert_eq!(shared.per_diem(), parse_amount("5").unwrap());
+        let other = TxRecord::from_str("Gym 300€ 30d 310121").unwrap();
+        let merged = copy.merge_same(&other).unwrap();
+        assert_eq!(merged.per_diem_raw(), merged.compute_per_diem(&today()));
+        let record =
+            TxRecord::from_string_record("2021-01-01T00:00:00+00:00::2021-02-01::Rent 280€ 1m")
+                .unwrap();
+        assert_eq!(record.per_diem(), parse_amount("10").unwrap());
+        // the same values as the computed ones
+        for spec in [
+            "Rent 1000€ 1m12x 310121",
+            "Coffee 1.3€",
+            "Car 20000€ 7y 290220",
+        ] {
+            let tx = TxRecord::from_str(spec).unwrap();
+            let computed = tx.compute_per_diem(&today());
+            assert_eq!(tx.per_diem_raw(), computed);
+            assert_eq!(tx.per_diem_raw(), computed);
+        }
+        // the records can be shared across threads
+        fn _assert_sync<T: Sync>() {}
+        _assert_sync::<TxRecord>();
+    }
+
+    #[test]
+    fn test_amount_kind() {
+        let tests = vec![
+            ("Rent 1000€ 1m12x", AmountKind::PerPeriod),
+            ("Groceries 50€ 1w2x", AmountKind::PerPeriod),
+            ("Insurance 1200€ 1y", AmountKind::Total),
+            ("Insurance 1200€ 12m1x", AmountKind::Total),
+            ("Coffee 2€", AmountKind::Total),
+            ("Rent 1000€ 1m 010122..311222", AmountKind::PerPeriod),
+        ];
+        // until cancelled, even before the first repeat is over
+        let tx = TxRecord::from_str("Rent 1000€ 1m+").unwrap();
+        assert_eq!(tx.get_amount_total(), tx.get_amount());
+        assert_eq!(tx.amount_kind(), AmountKind::PerPeriod);
+        for (spec, kind) in tests {
+            let tx = TxRecord::from_str(spec).unwrap();
+            assert_eq!(tx.amount_kind(), kind, "{}", spec);
+            // the total is the amount only when the amount is the total
+            assert_eq!(
+                tx.get_amount_total() == tx.get_amount(),
+                kind == AmountKind::Total,
+                "{}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn test_amount_on() {
+        let tx = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        let per_diem = parse_amount("10").unwrap();
+        // the day before the start
+        assert_eq!(tx.amount_on(&date(31, 12, 2020)), BigDecimal::zero());
+        // the start and the end day
+        assert_eq!(tx.amount_on(&date(1, 1, 2021)), per_diem);
+        assert_eq!(tx.amount_on(&date(15, 1, 2021)), per_diem);
+        assert_eq!(tx.amount_on(&date(31, 1, 2021)), per_diem);
+        // the day after the end
+        assert_eq!(tx.amount_on(&date(1, 2, 2021)), BigDecimal::zero());
+        // a paused day
+        let tx = TxRecord::from_str("Gym 300€ 30d 010121 paused=110121..200121").unwrap();
+        assert_eq!(
+            tx.amount_on(&date(10, 1, 2021)),
+            parse_amount("15").unwrap()
+        );
+        assert_eq!(tx.amount_on(&date(11, 1, 2021)), BigDecimal::zero());
+        assert_eq!(tx.amount_on(&date(20, 1, 2021)), BigDecimal::zero());
+        assert_eq!(
+            tx.amount_on(&date(21, 1, 2021)),
+            parse_amount("15").unwrap()
+        );
+        // a single day
+        let tx = TxRecord::from_str("Coffee 2€ 010121").unwrap();
+        assert_eq!(tx.amount_on(&date(1, 1, 2021)), parse_amount("2").unwrap());
+        assert_eq!(tx.amount_on(&date(2, 1, 2021)), BigDecimal::zero());
+    }
+
+    #[test]
+    fn test_sinking_fund() {
+        let monthly = Lifetime::from_str("1m").unwrap();
+        let tests = vec![
+            // one year, monthly
+            ("1200", (1, 1, 2022), (1, 1, 2023), "100.00"),
+            ("1200", (1, 1, 2022), (31, 12, 2022), "100.00"),
+            ("1200", (15, 3, 2022), (15, 3, 2023), "100.00"),
+            // rounded up to reach the target
+            ("1000", (1, 1, 2022), (1, 1, 2023), "83.34"),
+            ("1", (1, 1, 2022), (1, 1, 2023), "0.09"),
+            // a partial period counts as a contribution
+            ("1300", (1, 1, 2022), (2, 1, 2023), "100.00"),
+            // less than a period
+            ("500", (1, 1, 2022), (2, 1, 2022), "500.00"),
+            ("500", (1, 1, 2022), (1, 2, 2022), "500.00"),
+        ];
+        for (target, (fd, fm, fy), (ud, um, uy), expected) in tests {
+            let got = sinking_fund(
+                parse_amount(target).unwrap(),
+                date(fd, fm, fy),
+                date(ud, um, uy),
+                monthly,
+            )
+            .unwrap();
+            assert_eq!(got.to_string(), expected, "{} {}/{}/{}", target, ud, um, uy);
+        }
+        // the contributions reach the target
+        let target = parse_amount("1000").unwrap();
+        let got =
+            sinking_fund(target.clone(), date(1, 1, 2022), date(1, 1, 2023), monthly).unwrap();
+        assert!(got.clone() * BigDecimal::from(12) >= target);
+        assert!((got - parse_amount("0.01").unwrap()) * BigDecimal::from(12) < target);
+        // the repetitions of the period are ignored
+        let got = sinking_fund(
+            parse_amount("1200").unwrap(),
+            date(1, 1, 2022),
+            date(1, 1, 2023),
+            Lifetime::from_str("1m3x").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(got.to_string(), "100.00");
+        // other periods
+        let got = sinking_fund(
+            parse_amount("530").unwrap(),
+            date(1, 1, 2022),
+            date(1, 1, 2023),
+            Lifetime::from_str("1w").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(got.to_string(), "10.00");
+        let got = sinking_fund(
+            parse_amount("1200").unwrap(),
+            date(1, 1, 2022),
+            date(1, 1, 2023),
+            Lifetime::from_str("3m").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(got.to_string(), "300.00");
+        // invalid
+        let tests = vec![
+            ("1200", (1, 1, 2023)),
+            ("1200", (1, 1, 2021)),
+            ("0", (1, 1, 2023)),
+            ("-100", (1, 1, 2024)),
+        ];
+        for (target, (ud, um, uy)) in tests {
+            assert!(sinking_fund(
+                parse_amount(target).unwrap(),
+                date(1, 1, 2023),
+                date(ud, um, uy),
+                monthly,
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_committed_cost() {
+        let txs = [
+            TxRecord::from_str("Rent 310€ 1m 010121").unwrap(),
+            TxRecord::from_str("Gym 280€ 1m 010221").unwrap(),
+            TxRecord::from_str("Car 365€ 1y 010621").unwrap(),
+        ];
+        let on = date(15, 1, 2021);
+        // with no horizon only the active ones
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 0),
+            cost_of_life(txs.iter(), &on)
+        );
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 0),
+            parse_amount("10").unwrap()
+        );
+        assert_eq!(
+            committed_cost(txs.iter(), &on, -10),
+            parse_amount("10").unwrap()
+        );
+        // the gym starts within the horizon
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 17),
+            parse_amount("20").unwrap()
+        );
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 16),
+            parse_amount("10").unwrap()
+        );
+        // a quarter
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 90),
+            parse_amount("20").unwrap()
+        );
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 365),
+            parse_amount("21").unwrap()
+        );
+        // the rent is over
+        let on = date(1, 2, 2021);
+        assert_eq!(
+            committed_cost(txs.iter(), &on, 90),
+            parse_amount("10").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_lifetime_start_end() {
+        let month = Lifetime::Month {
+            amount: 1,
+            times: 1,
+        };
+        let tests = vec![
+            ((1, 1, 2022), (31, 12, 2023), month, Ok("1m24x")),
+            ((1, 1, 2022), (31, 1, 2022), month, Ok("1m1x")),
+            ((1, 1, 2022), (1, 1, 2022), month, Ok("1m1x")),
+            // partial periods are rounded up
+            ((1, 1, 2022), (15, 2, 2022), month, Ok("1m2x")),
+            ((31, 1, 2022), (29, 4, 2022), month, Ok("1m3x")),
+            (
+                (1, 1, 2022),
+                (31, 12, 2022),
+                "1w".parse().unwrap(),
+                Ok("1w53x"),
+            ),
+            (
+                (1, 1, 2022),
+                (10, 1, 2022),
+                "2d".parse().unwrap(),
+                Ok("2d5x"),
+            ),
+            (
+                (1, 1, 2022),
+                (31, 12, 2031),
+                "1y".parse().unwrap(),
+                Ok("1y10x"),
+            ),
+            ((1, 1, 2022), (1, 1, 2022), Lifetime::SingleDay, Ok("1d1x")),
+            // end before start
+            ((1, 1, 2022), (31, 12, 2021), month, Err(())),
+        ];
+        for (i, (start, end, period, expected)) in tests.into_iter().enumerate() {
+            let (start, end) = (date(start.0, start.1, start.2), date(end.0, end.1, end.2));
+            let got = Lifetime::from_start_end_period(&start, &end, &period);
+            match expected {
+                Ok(exp) => assert_eq!(
+                    got.map(|l| l.to_string()).ok().as_deref(),
+                    Some(exp),
+                    "test_lifetime_start_end#{}",
+                    i
+                ),
+                Err(_) => assert!(got.is_err(), "test_lifetime_start_end#{}", i),
+            }
+        }
+        // in a spec
+        let tx = TxRecord::from_str("Rent 100€ 1m 010122..311223 #home").unwrap();
+        assert_eq!(tx.get_starts_on(), date(1, 1, 2022));
+        assert_eq!(tx.get_ends_on(), date(31, 12, 2023));
+        assert_eq!(tx.get_amount_total(), parse_amount("2400").unwrap());
+        let tx = TxRecord::from_str("Rent 100€ 1m 010122 ..300622").unwrap();
+        assert_eq!(tx.get_lifetime().to_string(), "1m6x");
+        assert!(TxRecord::from_str("Rent 100€ 1m 010122..311221").is_err());
+        assert!(TxRecord::from_str("Rent 100€ 1m 010122..321221").is_err());
+    }
+
+    #[test]
+    fn test_lifetime_cap() {
+        // the maximum
+        assert!("1000y".parse::<Lifetime>().is_ok());
+        assert!("100y10x".parse::<Lifetime>().is_ok());
+        assert!("52000w".parse::<Lifetime>().is_ok());
+        // over the maximum
+        let tests = vec![
+            "1001y",
+            "999999999y",
+            "100y11x",
+            "99999999999999999999999999y",
+            "1y99999999999999999999999999x",
+            "9999999w9999999x",
+            "365250d2x",
+        ];
+        for t in tests {
+            match t.parse::<Lifetime>() {
+                Err(CostOfLifeError::InvalidLifetimeFormat(_)) => {}
+                r => panic!("{} should be an invalid lifetime, got {:?}", t, r),
+            }
+        }
+        // in a spec
+        assert!(TxRecord::from_str("Forever 10€ 999999999y").is_err());
+        // large amounts times large repeats
+        let tx = TxRecord::from_str("Big 1000000000€ 1d365000x").unwrap();
+        assert_eq!(
+            tx.get_amount_total(),
+            parse_amount("365000000000000").unwrap()
+        );
+        assert_eq!(to_f32_safe(&tx.get_amount_total()), 365000000000000.0);
+        assert!(to_f32_safe(&tx.per_diem()).is_finite());
+        let tests = vec![
+            "Big 10000000000€ 1d365000x",
+            "Big 1000000000000000.01€",
+            "Big -1000000000000000.01€",
+            "Big 99999999999999999999999999999999999999999999999999€ 1y1000x",
+        ];
+        for t in tests {
+            match TxRecord::from_str(t) {
+                Err(CostOfLifeError::InvalidAmount(_)) => {}
+                r => panic!("{} should be an invalid amount, got {:?}", t, r),
+            }
+        }
+        // errors can be displayed
+        let e = "1001y".parse::<Lifetime>().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "invalid lifetime format: lifetime 1001y exceeds the maximum of 1000 years"
+        );
+    }
+
+    #[test]
+    fn test_lifetime_from_duration() {
+        let tests = vec![
+            (
+                Duration::days(7),
+                Lifetime::Week {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                Duration::days(10),
+                Lifetime::Day {
+                    amount: 10,
+                    times: 1,
+                },
+            ),
+            (
+                Duration::days(1),
+                Lifetime::Day {
+                    amount: 1,
+                    times: 1,
+                },
+            ),
+            (
+                Duration::weeks(52),
+                Lifetime::Week {
+                    amount: 52,
+                    times: 1,
+                },
+            ),
+            (
+                Duration::hours(50),
+                Lifetime::Day {
+                    amount: 2,
+                    times: 1,
+                },
+            ),
+        ];
+        for (d, exp) in tests {
+            let got = Lifetime::from_duration(d).unwrap();
+            assert_eq!(got, exp);
+            assert_eq!(format!("{}", got), format!("{}", exp));
+        }
+        // errors
+        let tests = vec![
+            Duration::zero(),
+            Duration::hours(23),
+            Duration::days(-7),
+            Duration::days(366_000),
+        ];
+        for d in tests {
+            match Lifetime::from_duration(d) {
+                Err(CostOfLifeError::InvalidLifetimeFormat(_)) => {}
+                r => panic!("{} should be an invalid duration, got {:?}", d, r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_lifetime_normalize() {
+        let tests = vec![
+            ("1d", "1d1x"),
+            ("7d", "1w1x"),
+            ("14d3x", "2w3x"),
+            ("10d", "10d1x"),
+            ("1w", "1w1x"),
+            ("12m", "1y1x"),
+            ("24m2x", "2y2x"),
+            ("1m12x", "1m12x"),
+            ("18m", "18m1x"),
+            ("3y", "3y1x"),
+        ];
+        for (input, canonical) in tests {
+            let lt = input.parse::<Lifetime>().unwrap();
+            assert_eq!(lt.normalize().to_string(), canonical);
+            // normalizing twice gives the same result
+            assert_eq!(lt.normalize().normalize().to_string(), canonical);
+        }
+        // single day
+        assert_eq!(Lifetime::SingleDay.normalize().to_string(), "1d1x");
+    }
+
+    #[test]
+    fn test_lifetime_equivalent_in() {
+        let tests = vec![
+            // month and week
+            ("1m", "1w", 4.35),
+            ("2w", "1m", 0.46),
+            ("1m3x", "1w", 13.05),
+            // year and month
+            ("1y", "1m", 12.0),
+            ("1m", "1y", 0.08),
+            ("6m", "1y", 0.5),
+            ("2y", "6m", 4.0),
+            // same unit
+            ("1w", "7d", 1.0),
+            ("1w4x", "2w", 2.0),
+            ("1m", "1m12x", 0.08),
+            // single day
+            ("1d", "1w", 0.14),
+            ("1w", "1d", 7.0),
+            ("1y", "1d", 365.25),
+            // until cancelled, a single period
+            ("1m+", "1w", 4.35),
+            ("1w", "2w+", 0.5),
+        ];
+        for (lt, unit, expected) in tests {
+            let got = Lifetime::from_str(lt)
+                .unwrap()
+                .equivalent_in(&Lifetime::from_str(unit).unwrap());
+            assert!((got - expected).abs() < 0.01, "{} in {}: {}", lt, unit, got);
+        }
+        assert_eq!(
+            Lifetime::SingleDay.equivalent_in(&Lifetime::from_str("1d").unwrap()),
+            1.0
+        );
+        assert_eq!(Lifetime::SingleDay.equivalent_in(&Lifetime::SingleDay), 1.0);
+        // inverse of each other
+        let (month, week) = (
+            Lifetime::from_str("1m").unwrap(),
+            Lifetime::from_str("1w").unwrap(),
+        );
+        let product = month.equivalent_in(&week) * week.equivalent_in(&month);
+        assert!((product - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_extract() {
+        // extract not matching date
+        // this cannot happen but anyway
+        let r = extract_date("invalid date");
+        assert_eq!(r.unwrap(), today());
+        // amounts embedded in noisy tokens
+        assert_eq!(extract_amount("20€"), Some(("20", "€")));
+        assert_eq!(extract_amount("~20€"), Some(("20", "€")));
+        assert_eq!(extract_amount("20€/month"), Some(("20", "€")));
+        assert_eq!(extract_amount("(20€)"), Some(("20", "€")));
+        assert_eq!(extract_amount("(20.50€)"), Some(("20.50", "€")));
+        // the minus sign must start the token
+        assert_eq!(extract_amount("-20€"), Some(("-20", "€")));
+        assert_eq!(extract_amount("-20.50EUR"), Some(("-20.50", "EUR")));
+        assert_eq!(extract_amount("~-20€"), Some(("20", "€")));
+        // not an amount
+        assert_eq!(extract_amount("20"), None);
+        assert_eq!(extract_amount("01.01.2020€"), None);
+        assert_eq!(extract_amount("20,2020€"), None);
+        assert_eq!(extract_amount("2BED"), None);
+        assert_eq!(extract_amount("12PRO"), None);
+        assert_eq!(extract_amount("10.5BHD"), Some(("10.5", "BHD")));
+        // full specs
+        let tx = TxRecord::from_str("Netflix ~20€/month #subscriptions").unwrap();
+        assert_eq!(tx.get_name(), "Netflix");
+        assert_eq!(tx.get_amount(), parse_amount("20").unwrap());
+        let tx = TxRecord::from_str("Gym (35.90€) #sport").unwrap();
+        assert_eq!(tx.get_name(), "Gym");
+        assert_eq!(tx.get_amount(), parse_amount("35.90").unwrap());
+        let tx = TxRecord::from_str("Refund -30€ 1m #phone").unwrap();
+        assert_eq!(tx.get_name(), "Refund");
+        assert_eq!(tx.get_amount(), parse_amount("-30").unwrap());
+        assert!(tx.per_diem() < BigDecimal::zero());
+        assert!(TxRecord::from_str("Refund -0€").is_err());
+    }
+
+    #[test]
+    fn test_hashtags() {
+        // simple and multi-word tags
+        assert_eq!(extract_hashtag("#home"), Some("home"));
+        assert_eq!(extract_hashtag("#\"home office\""), Some("home office"));
+        assert_eq!(extract_hashtag("#[home office]"), Some("home office"));
+        assert_eq!(extract_hashtag("#\"\""), None);
+        assert_eq!(extract_hashtag("#\"home office"), None);
+        // tokenizer
+        let tokens =
+            tokenize("Chair  300€ #\"home office\" #[work stuff] #desk").collect::<Vec<&str>>();
+        assert_eq!(
+            tokens,
+            vec![
+                "Chair",
+                "300€",
+                "#\"home office\"",
+                "#[work stuff]",
+                "#desk"
+            ]
+        );
+        // parsing
+        let tx = TxRecord::from_str("Office chair 300€ #\"home office\" #[work stuff] #furniture")
+            .unwrap();
+        assert_eq!(tx.get_name(), "Office chair");
+        assert_eq!(
+            tx.get_tags(),
+            vec!["furniture", "home office", "work stuff"]
+        );
+        assert!(tx.has_tag("home office"));
+        assert!(tx.has_tag("home-office"));
+        assert!(tx.has_tag("work stuff"));
+        assert!(!tx.has_tag("home"));
+        // unicode tags
+        assert_eq!(extract_hashtag("#café"), Some("café"));
+        assert_eq!(extract_hashtag(".日本語"), Some("日本語"));
+        assert_eq!(extract_hashtag("#über-2"), Some("über-2"));
+        assert_eq!(extract_hashtag("#2über"), None);
+        let tx = TxRecord::from_str("Lunch 12€ #café .日本語 #Ελλάδα").unwrap();
+        assert_eq!(tx.get_name(), "Lunch");
+        assert_eq!(tx.get_tags(), vec!["café", "Ελλάδα", "日本語"]);
+        assert!(tx.has_tag("café"));
+        assert!(tx.has_tag("cafe"));
+        assert!(tx.has_tag("日本語"));
+        assert!(tx.has_tag("Ελλάδα"));
+        assert!(!tx.has_tag("caf"));
+        // the dot form is the same as the hash form
+        assert_eq!(extract_hashtag(".transport"), Some("transport"));
+        assert_eq!(extract_hashtag(".\"home office\""), Some("home office"));
+        assert_eq!(extract_hashtag(".[home office]"), Some("home office"));
+        assert_eq!(extract_hashtag(".gitignore"), Some("gitignore"));
+        assert_eq!(extract_hashtag(".5"), None);
+        assert_eq!(extract_hashtag(".5€"), None);
+        let dot = TxRecord::from_str("Bike 1900€ 10y 210320 .bike .Home_Office").unwrap();
+        let hash = TxRecord::from_str("Bike 1900€ 10y 210320 #bike #Home_Office").unwrap();
+        assert_eq!(dot, hash);
+        assert_eq!(dot.get_tags(), vec!["Home_Office", "bike"]);
+        assert!(dot.has_tag("home-office"));
+        assert_eq!(dot.to_spec_string(), hash.to_spec_string());
+        // a filename like token is a tag, not part of the name
+        let tx = TxRecord::from_str("Repo 5€ .gitignore").unwrap();
+        assert_eq!(tx.get_name(), "Repo");
+        assert_eq!(tx.get_tags(), vec!["gitignore"]);
+        // a dot in front of a number is not a tag
+        let tx = TxRecord::from_str("Ticket .5 #1 2€").unwrap();
+        assert_eq!(tx.get_name(), "Ticket .5 #1");
+        assert!(tx.get_tags().is_empty());
+        let tx = TxRecord::from_str("Coffee 0.5€ .coffee").unwrap();
+        assert_eq!(tx.get_amount(), BigDecimal::from_str("0.50").unwrap());
+        assert_eq!(tx.get_tags(), vec!["coffee"]);
+        // but it is when the number is another role
+        assert!(TxRecord::from_str("Coffee .5€").is_err());
+        assert!(TxRecord::from_str("Coffee 2€ .1m").is_err());
+        assert!(TxRecord::from_str("Coffee 2€ .010121").is_err());
+        // the date range is not a tag
+        let tx = TxRecord::from_str("Rent 100€ 1m 010122 ..311222").unwrap();
+        assert!(tx.get_tags().is_empty());
+        assert_eq!(tx.get_lifetime().get_repeats(), 12);
+        // an unterminated quote is part of the name
+        let tx = TxRecord::from_str("Office #\"chair 300€").unwrap();
+        assert_eq!(tx.get_name(), "Office #\"chair");
+        assert!(tx.get_tags().is_empty());
+        // round trip
+        let tx = TxRecord::from(
+            "Office chair",
+            vec!["home office", "furniture"],
+            "300",
+            date(1, 1, 2021),
+            Lifetime::SingleDay,
+            now_local(),
+            None,
+        )
+        .unwrap();
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(tx, txr);
+        assert_eq!(txr.get_tags(), vec!["furniture", "home office"]);
+        // the unicode normalization forms of a tag are the same tag
+        let (nfc, nfd) = ("caf\u{e9}", "cafe\u{301}");
+        assert_eq!(tag_slug(nfc), tag_slug(nfd));
+        let tx = TxRecord::from_str(&format!("Coffee 2€ #{}", nfd)).unwrap();
+        assert_eq!(tx.get_tags(), vec![nfc]);
+        assert!(tx.has_tag(nfc));
+        assert!(tx.has_tag(nfd));
+        let tx = TxRecord::from_str(&format!("Coffee 2€ #{} #{}", nfc, nfd)).unwrap();
+        assert_eq!(tx.get_tags().len(), 1);
+        let (nfc, nfd) = ("\u{d55c}", "\u{1112}\u{1161}\u{11ab}");
+        assert_eq!(tag_slug(nfc), tag_slug(nfd));
+        let tx = TxRecord::from_str(&format!("Kimchi 5€ .{}", nfd)).unwrap();
+        assert!(tx.has_tag(nfc));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let tx = TxRecord::from_str("Book 20€ vendor=Amazon account=checking #books").unwrap();
+        assert_eq!(tx.get_name(), "Book");
+        assert_eq!(tx.get_amount(), parse_amount("20").unwrap());
+        assert_eq!(tx.get_tags(), vec!["books"]);
+        assert_eq!(tx.get_meta("vendor"), Some("Amazon"));
+        assert_eq!(tx.get_meta("account"), Some("checking"));
+        assert_eq!(tx.get_meta("whatever"), None);
+        assert_eq!(
+            tx.get_metadata(),
+            vec![
+                ("account".to_string(), "checking".to_string()),
+                ("vendor".to_string(), "Amazon".to_string())
+            ]
+        );
+        // tokens that are not key=value stay in the name
+        let tx = TxRecord::from_str("A = B 20€ =x y=").unwrap();
+        assert_eq!(tx.get_name(), "A = B =x y=");
+        assert!(tx.get_metadata().is_empty());
+        // round trip
+        let mut tx = TxRecord::new("Book", "20").unwrap();
+        tx.meta.insert("vendor".to_string(), "Amazon".to_string());
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(txr.get_meta("vendor"), Some("Amazon"));
+        assert_eq!(tx, txr);
+    }
+
+    #[test]
+    fn test_relative_start() {
+        let tests = vec![
+            ("Rent 1000€ 1m +30d", today() + Duration::days(30)),
+            ("Rent 1000€ +2w 1m", today() + Duration::weeks(2)),
+            (
+                "Rent 1000€ 1m12x +1m",
+                today()
+                    + Duration::days(Lifetime::from_str("1m").unwrap().get_days_since(&today())),
+            ),
+            (
+                "Insurance 300€ +1y 1y",
+                today()
+                    + Duration::days(Lifetime::from_str("1y").unwrap().get_days_since(&today())),
+            ),
+        ];
+        for (s, starts_on) in tests {
+            let tx = TxRecord::from_str(s).unwrap();
+            assert_eq!(tx.get_starts_on(), starts_on);
+            assert_eq!(tx.get_name(), s.split(' ').next().unwrap());
+        }
+        // the lifetime is not affected
+        let tx = TxRecord::from_str("Rent 1000€ 1m12x +30d").unwrap();
+        assert_eq!(
+            tx.get_lifetime(),
+            &Lifetime::Month {
+                amount: 1,
+                times: 12
+            }
+        );
+        let tx = TxRecord::from_str("Rent 1000€ +30d").unwrap();
+        assert_eq!(tx.get_lifetime(), &Lifetime::SingleDay);
+        // invalid offsets
+        assert!(TxRecord::from_str("Rent 1000€ +9999y").is_err());
+        let tx = TxRecord::from_str("Rent 1000€ +0d").unwrap();
+        assert_eq!(tx.get_name(), "Rent +0d");
+        assert_eq!(tx.get_starts_on(), today());
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut tx = TxRecord::from_str("Gym 300€ 30d 010121").unwrap();
+        tx.add_pause(date(11, 1, 2021), date(20, 1, 2021)).unwrap();
+        // the end date does not move, the cost is spread on the active days
+        assert_eq!(tx.get_ends_on(), date(30, 1, 2021));
+        assert_eq!(tx.get_duration_days(), 30);
+        assert_eq!(tx.get_active_days(), 20);
+        assert_eq!(tx.per_diem(), BigDecimal::from_str("15.00").unwrap());
+        assert!(tx.is_active_on(&date(10, 1, 2021)));
+        assert!(!tx.is_active_on(&date(11, 1, 2021)));
+        assert!(!tx.is_active_on(&date(20, 1, 2021)));
+        assert!(tx.is_active_on(&date(21, 1, 2021)));
+        assert_eq!(
+            cost_of_life([tx.clone()].iter(), &date(15, 1, 2021)),
+            BigDecimal::zero()
+        );
+        assert_eq!(
+            cost_of_life([tx.clone()].iter(), &date(21, 1, 2021)),
+            BigDecimal::from_str("15").unwrap()
+        );
+        // elapsed and accrued skip the paused days
+        assert_eq!(tx.days_elapsed(Some(date(15, 1, 2021))), 10);
+        assert_eq!(tx.days_elapsed(Some(date(25, 1, 2021))), 15);
+        assert_eq!(tx.days_elapsed(Some(date(1, 3, 2021))), 20);
+        assert_eq!(
+            tx.accrued_to(Some(date(15, 1, 2021))),
+            BigDecimal::from_str("150.00").unwrap()
+        );
+        assert_eq!(
+            tx.accrued_to(Some(date(1, 3, 2021))),
+            BigDecimal::from_str("300.00").unwrap()
+        );
+        assert_eq!(
+            tx.cost_over_range(&date(1, 1, 2021), &date(31, 1, 2021)),
+            BigDecimal::from_str("300.00").unwrap()
+        );
+        assert_eq!(
+            tx.cost_over_range(&date(11, 1, 2021), &date(20, 1, 2021)),
+            BigDecimal::zero()
+        );
+        // overlapping and adjacent pauses are merged
+        tx.add_pause(date(18, 1, 2021), date(22, 1, 2021)).unwrap();
+        tx.add_pause(date(23, 1, 2021), date(23, 1, 2021)).unwrap();
+        assert_eq!(tx.get_pauses(), &[(date(11, 1, 2021), date(23, 1, 2021))]);
+        assert_eq!(tx.get_active_days(), 17);
+        // invalid pauses
+        assert!(tx.add_pause(date(2, 1, 2021), date(1, 1, 2021)).is_err());
+        assert!(tx.add_pause(date(1, 1, 2021), date(31, 1, 2021)).is_err());
+        assert_eq!(tx.get_pauses().len(), 1);
+        // the pauses are part of the spec
+        tx.add_pause(date(1, 1, 2021), date(2, 1, 2021)).unwrap();
+        let spec = tx.to_spec_string();
+        assert!(spec.contains("paused=010121..020121,110121..230121"));
+        let got = TxRecord::from_str(&spec).unwrap();
+        assert_eq!(got, tx);
+        assert!(got.get_meta("paused").is_none());
+        let got = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(got.get_pauses(), tx.get_pauses());
+        assert!(TxRecord::from_str("Gym 300€ 30d 010121 paused=..200121").is_err());
+        assert!(TxRecord::from_str("Gym 300€ 30d 010121 paused=010121..300121").is_err());
+        // serialization is backward compatible
+        let json = serde_json::to_string(&TxRecord::from_str("Gym 300€ 30d").unwrap()).unwrap();
+        assert!(!json.contains("pauses"));
+        let got: TxRecord = serde_json::from_str(&json).unwrap();
+        assert!(got.get_pauses().is_empty());
+        let got: TxRecord = serde_json::from_str(&serde_json::to_string(&tx).unwrap()).unwrap();
+        assert_eq!(got, tx);
+    }
+
+    #[test]
+    fn test_canonical_record() {
+        let recorded_at = "2021-01-03T19:36:43+00:00";
+        let a = TxRecord::from_string_record(&format!(
+            "{}::2021-01-01::Rent 1000€ 1m12x 010121 #home .bills vendor=Acme ::Housing",
+            recorded_at
+        ))
+        .unwrap();
+        let b = TxRecord::from_string_record(&format!(
+            "{}::2021-01-01::::Housing .bills Rent vendor=Acme 1 month 12x #home 1000.00EUR",
+            recorded_at
+        ))
+        .unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.to_string_record(), b.to_string_record());
+        assert_eq!(
+            a.to_string_record(),
+            format!(
+                "{}::2021-01-01::Rent 1000.00€ 1m12x ::Housing #bills #home vendor=Acme\n",
+                recorded_at
+            )
+        );
+        // the canonical form is stable
+        let c = TxRecord::from_string_record(&a.to_string_record()).unwrap();
+        assert_eq!(c, a);
+        assert_eq!(c.to_string_record(), a.to_string_record());
+    }
+
+    #[test]
+    fn test_token_order() {
+        let (starts_on, lifetime) = (date(1, 1, 2018), Lifetime::from_str("100d").unwrap());
+        let tests = vec![
+            "Car 2020€ 010118 100d",
+            "010118 Car 100d 2020€",
+            "Car 100d 010118 2020€ #cars",
+            "Car 2020€ 100d .cars 010118 // 010118",
+            "2020€ 100d 010118 Car",
+            "Car 010118 100d 2020EUR ::Transport",
+        ];
+        for spec in tests {
+            let tx = TxRecord::from_str(spec).unwrap();
+            assert_eq!(tx.get_name(), "Car", "{}", spec);
+            assert_eq!(tx.get_starts_on(), starts_on, "{}", spec);
+            assert_eq!(tx.get_lifetime(), &lifetime, "{}", spec);
+            assert_eq!(tx.get_amount(), parse_amount("2020").unwrap(), "{}", spec);
+        }
+        let today = date(15, 6, 2021);
+        assert_eq!(
+            Token::classify("010118", &today).unwrap(),
+            Token::StartDate(starts_on)
+        );
+        assert!(matches!(
+            Token::classify("100d", &today).unwrap(),
+            Token::Lifetime(_)
+        ));
+        // six digits that are not a date are a word
+        assert_eq!(
+            Token::classify("123456", &today).unwrap(),
+            Token::Word("123456")
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_tokens() {
+        let on = date(10, 1, 2021);
+        // each token has one role
+        assert_eq!(
+            Token::classify("10€", &on).unwrap(),
+            Token::Amount("10", "€")
+        );
+        assert_eq!(Token::classify("#home", &on).unwrap(), Token::Tag("home"));
+        assert_eq!(
+            Token::classify("vendor=Acme", &on).unwrap(),
+            Token::Meta("vendor", "Acme")
+        );
+        assert_eq!(
+            Token::classify("010121", &on).unwrap(),
+            Token::StartDate(date(1, 1, 2021))
+        );
+        assert_eq!(
+            Token::classify("+1d", &on).unwrap(),
+            Token::StartDate(date(11, 1, 2021))
+        );
+        assert_eq!(Token::classify("2021", &on).unwrap(), Token::Word("2021"));
+        // words that contain a lifetime or a date are part of the name
+        let tx = TxRecord::from_str_on("R2D2 Lego 10€", &on).unwrap();
+        assert_eq!(tx.get_name(), "R2D2 Lego");
+        assert_eq!(tx.get_lifetime(), &Lifetime::SingleDay);
+        let tx = TxRecord::from_str_on("Invoice A0101210 10€", &on).unwrap();
+        assert_eq!(tx.get_name(), "Invoice A0101210");
+        assert_eq!(tx.get_starts_on(), on);
+        // the last category wins
+        let tx = TxRecord::from_str_on("Rent 10€ ::Home ::Housing", &on).unwrap();
+        assert_eq!(tx.get_category(), Some("Housing"));
+        // a date range without start goes with a start date
+        let tx = TxRecord::from_str_on("Rent 10€ 1m 010121 ..310321", &on).unwrap();
+        assert_eq!(tx.get_lifetime().get_repeats(), 3);
+        // ambiguous or conflicting tokens
+        let tests = vec![
+            ("Rent 10€ 20€", "the amount is set twice: 20€"),
+            ("Rent 10€ 1m 2w", "the lifetime is set twice: 2w"),
+            (
+                "Rent 10€ 010121 020121",
+                "the start date is set twice: 020121",
+            ),
+            ("Rent 10€ 010121 +1d", "the start date is set twice: +1d"),
+            (
+                "Rent 10€ 1st-mon 010121",
+                "the start date is set twice: 010121",
+            ),
+            (
+                "Rent 10€ 010121 020121..311221",
+                "the start date is set twice: 020121..311221",
+            ),
+            (
+                "Rent 10€ ..311221 ..301221",
+                "the end date is set twice: ..301221",
+            ),
+            ("Rent 10€ /2 /3", "the share is set twice: /3"),
+            (
+                "Rent 10€ #1m",
+                "invalid tag, tags must start with a letter: #1m",
+            ),
+            (
+                "Rent 10€ .2d",
+                "invalid tag, tags must start with a letter: .2d",
+            ),
+            (
+                "Rent 10€ deposit=100€",
+                "ambiguous token, it is both an amount and a metadata: deposit=100€",
+            ),
+            ("Rent 10€ 310221", "the date does not exist: 310221"),
+        ];
+        for (spec, err) in tests {
+            match TxRecord::from_str_on(spec, &on) {
+                Ok(tx) => panic!("{} should not parse: {:?}", spec, tx),
+                Err(e) => assert!(e.to_string().ends_with(err), "{}: {}", spec, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let tx = TxRecord::from_str("Rent 1000€ 1m3x 310121").unwrap();
+        let amount = BigDecimal::from_str("1000.00").unwrap();
+        assert_eq!(
+            tx.occurrences().collect::<Vec<(NaiveDate, BigDecimal)>>(),
+            vec![
+                (date(31, 1, 2021), amount.clone()),
+                (date(28, 2, 2021), amount.clone()),
+                (date(31, 3, 2021), amount),
+            ]
+        );
+        let tx = TxRecord::from_str("Groceries 50€ 2w26x 040121").unwrap();
+        assert_eq!(tx.occurrences().count(), 26);
+        assert_eq!(tx.occurrences().nth(1).unwrap().0, date(18, 1, 2021));
+        assert_eq!(tx.occurrences().last().unwrap().0, date(20, 12, 2021));
+        // a single occurrence
+        let tx = TxRecord::from_str("Coffee 2€ 010121").unwrap();
+        assert_eq!(
+            tx.occurrences().collect::<Vec<(NaiveDate, BigDecimal)>>(),
+            vec![(date(1, 1, 2021), BigDecimal::from_str("2.00").unwrap())]
+        );
+        let tx = TxRecord::from_str("Car 20000€ 10y 010121").unwrap();
+        assert_eq!(tx.occurrences().count(), 1);
+    }
+
+    #[test]
+    fn test_indefinite() {
+        let lt = Lifetime::from_str("1m+").unwrap();
+        assert_eq!(
+            lt,
+            Lifetime::Indefinite {
+                amount: 1,
+                period: Period::Month
+            }
+        );
+        assert!(lt.is_indefinite());
+        assert_eq!(lt.to_string(), "1m+");
+        assert_ne!(lt, Lifetime::from_str("1m").unwrap());
+        assert_eq!(
+            Lifetime::from_str("12m+").unwrap().normalize().to_string(),
+            "1y+"
+        );
+        assert_eq!(
+            Lifetime::from_str("14d+").unwrap().normalize().to_string(),
+            "2w+"
+        );
+        // rolled over to cover a date
+        let on = date(21, 1, 2021);
+        let tx = TxRecord::from_str("Cleaning 70€ 1w+ 010121").unwrap();
+        assert_eq!(tx.get_ends_on_at(&on), on);
+        assert_eq!(tx.get_amount_total_at(&on), parse_amount("210").unwrap());
+        assert_eq!(tx.per_diem_at(&on), parse_amount("10").unwrap());
+        assert_eq!(tx.occurrences_at(&on).count(), 3);
+        // without a date, the first repeat
+        assert_eq!(tx.get_ends_on(), date(7, 1, 2021));
+        assert_eq!(tx.get_amount_total(), parse_amount("70").unwrap());
+        assert_eq!(tx.get_duration_days(), 7);
+        assert_eq!(tx.per_diem(), parse_amount("10").unwrap());
+        assert_eq!(tx.occurrences().count(), 1);
+        assert!(!tx.amount_is_total());
+        // active forever
+        assert!(tx.is_active_on(&(on + Duration::days(3650))));
+        assert!(!tx.is_active_on(&date(31, 12, 2020)));
+        assert_eq!(
+            cost_of_life([tx.clone()].iter(), &(on + Duration::days(365))),
+            parse_amount("10").unwrap()
+        );
+        assert_eq!(
+            tx.cost_over_range(&on, &(on + Duration::days(9))),
+            parse_amount("100").unwrap()
+        );
+        // the record keeps the lifetime
+        let record = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(record.get_lifetime(), &Lifetime::from_str("1w+").unwrap());
+        assert!(record.to_string_record().ends_with(" 1w+\n"));
+        // an ongoing rent added years ago
+        let tx = TxRecord::from_str("Rent 1000€ 1m+ 010120").unwrap();
+        let on = date(10, 6, 2022);
+        assert!(tx.is_active_on(&on));
+        assert_eq!(tx.get_ends_on_at(&on), date(30, 6, 2022));
+        assert_eq!(tx.get_ends_on(), date(31, 1, 2020));
+        // rolled over to cover the query date, not today
+        let tx = TxRecord::from_str("Rent 1000€ 1m+ 010121").unwrap();
+        let tests = vec![
+            // before the start, the first period
+            ((1, 12, 2020), (31, 1, 2021), "1000", "32.26"),
+            ((15, 1, 2021), (31, 1, 2021), "1000", "32.26"),
+            ((31, 1, 2021), (31, 1, 2021), "1000", "32.26"),
+            ((1, 2, 2021), (28, 2, 2021), "2000", "33.90"),
+            ((15, 3, 2021), (31, 3, 2021), "3000", "33.33"),
+            ((31, 12, 2021), (31, 12, 2021), "12000", "32.88"),
+        ];
+        for ((d, m, y), (ed, em, ey), total, per_diem) in tests {
+            let on = date(d, m, y);
+            assert_eq!(tx.get_ends_on_at(&on), date(ed, em, ey), "{}", on);
+            assert_eq!(
+                tx.get_amount_total_at(&on),
+                parse_amount(total).unwrap(),
+                "{}",
+                on
+            );
+            assert_eq!(tx.per_diem_at(&on).to_string(), per_diem, "{}", on);
+        }
+        let on = date(15, 3, 2021);
+        assert_eq!(
+            tx.get_duration_days_at(&on),
+            (date(31, 3, 2021) - date(1, 1, 2021)).num_days() + 1
+        );
+        assert_eq!(
+            cost_of_life([tx.clone()].iter(), &on),
+            parse_amount("33.33").unwrap()
+        );
+        assert_eq!(tx.amount_on(&on), tx.per_diem_raw_at(&on));
+        assert_eq!(
+            tx.cost_over_range(&date(1, 3, 2021), &on),
+            parse_amount("500").unwrap()
+        );
+        assert_eq!(tx.get_progress(Some(date(16, 1, 2021))), 0.5);
+        assert_eq!(tx.days_elapsed(Some(date(10, 1, 2021))), 10);
+        assert_eq!(
+            tx.accrued_to(Some(date(10, 1, 2021))),
+            parse_amount("322.58").unwrap()
+        );
+        // a future start covers a single period
+        let tx = TxRecord::from_str("Rent 1000€ 1w+ 110121").unwrap();
+        let on = date(1, 1, 2021);
+        assert_eq!(tx.get_ends_on_at(&on), date(17, 1, 2021));
+        assert_eq!(tx.get_amount_total_at(&on), parse_amount("1000").unwrap());
+    }
+
+    #[test]
+    fn test_per_period() {
+        let tx = TxRecord::from_str("Rent 3650€ 365d 010121").unwrap();
+        assert_eq!(tx.per_diem(), parse_amount("10").unwrap());
+        assert_eq!(tx.per_period(&Period::Day), tx.per_diem());
+        assert_eq!(tx.per_week(), parse_amount("70").unwrap());
+        assert_eq!(tx.per_month(), parse_amount("304.38").unwrap());
+        assert_eq!(tx.annual_cost(), parse_amount("3652.50").unwrap());
+        let tx = TxRecord::from_str("Ramen 1000¥ 010121").unwrap();
+        assert_eq!(tx.per_month(), parse_amount("30438").unwrap());
+        // periods
+        let tests = vec![
+            ("day", Period::Day),
+            ("Week", Period::Week),
+            ("m", Period::Month),
+            ("YEAR", Period::Year),
+        ];
+        for (s, p) in tests {
+            assert_eq!(s.parse::<Period>().unwrap(), p);
+            assert_eq!(p.to_string().parse::<Period>().unwrap(), p);
+        }
+        assert!("fortnight".parse::<Period>().is_err());
+        // cost of life
+        let txs = [
+            TxRecord::from_str("Rent 1000€ 1m 010121").unwrap(),
+            TxRecord::from_str("Phone 31€ 1m 010121").unwrap(),
+        ];
+        let on = date(10, 1, 2021);
+        assert_eq!(
+            cost_of_life_per(txs.iter(), &on, &Period::Day),
+            cost_of_life(txs.iter(), &on)
+        );
+        assert_eq!(
+            cost_of_life_per(txs.iter(), &on, &Period::Month),
+            parse_amount("1012.29").unwrap()
+        );
+        assert_eq!(
+            cost_of_life_per(txs.iter(), &date(10, 2, 2021), &Period::Year),
+            BigDecimal::zero()
+        );
+    }
+
+    #[test]
+    fn test_merge_same() {
+        let a = TxRecord::from_str("Netflix 10€ 1m3x 010121 #movies vendor=netflix ::Fun").unwrap();
+        let b = TxRecord::from_str("Netflix 10€ 1m3x 010321 #tv vendor=other ::Leisure").unwrap();
+        // overlapping, the overlap is counted once
+        let m = a.merge_same(&b).unwrap();
+        assert_eq!(m.get_starts_on(), date(1, 1, 2021));
+        assert_eq!(m.get_ends_on(), date(31, 5, 2021));
+        assert_eq!(m.get_lifetime().to_string(), "1m5x");
+        assert_eq!(m.get_amount_total(), parse_amount("50").unwrap());
+        assert_eq!(m.get_tags(), vec!["movies", "tv"]);
+        assert_eq!(m.get_meta("vendor"), Some("netflix"));
+        assert_eq!(m.get_category(), Some("Fun"));
+        // the order does not matter
+        assert_eq!(
+            b.merge_same(&a).unwrap().to_spec_string(),
+            m.to_spec_string()
+        );
+        // contiguous
+        let a = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        let b = TxRecord::from_str("Rent 280€ 1m 010221").unwrap();
+        assert!(a.merge_same(&b).is_none());
+        let b = TxRecord::from_str("Rent 310€ 1m 010221").unwrap();
+        assert_eq!(a.merge_same(&b).unwrap().get_lifetime().to_string(), "1m2x");
+        // a record inside the other
+        let b = TxRecord::from_str("Rent 310€ 1m 010121").unwrap();
+        assert_eq!(a.merge_same(&b).unwrap().get_lifetime().to_string(), "1m1x");
+        // not mergeable
+        let tests = vec![
+            "Rent 310€ 1m 020221",
+            "Rent 310€ 31d 010221",
+            "Flat 310€ 1m 010221",
+            "Rent 310USD 1m 010221",
+            "Rent 310€ 1m+ 010221",
+            "Rent 310€ 1m 010221 paused=050221..060221",
+        ];
+        for spec in tests {
+            let b = TxRecord::from_str(spec).unwrap();
+            assert!(a.merge_same(&b).is_none(), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_clone_with_tags() {
+        let tx = TxRecord::from_str("Rent 1000€ 1m 010121 #home #fixed ::Housing").unwrap();
+        let txc = tx.clone_with_tags(vec!["flat", "Rent Share"]);
+        assert_eq!(txc.get_tags(), vec!["Rent Share", "flat"]);
+        assert_eq!(tx.get_tags(), vec!["fixed", "home"]);
+        assert_eq!(txc.get_name(), tx.get_name());
+        assert_eq!(txc.get_amount(), tx.get_amount());
+        assert_eq!(txc.get_starts_on(), tx.get_starts_on());
+        assert_eq!(txc.get_category(), Some("Housing"));
+        // tags are slugified
+        let txc = tx.clone_with_tags(vec!["Home", "home"]);
+        assert_eq!(txc.get_tags().len(), 1);
+        // no tags
+        let txc = tx.clone_with_tags(vec![]);
+        assert!(txc.get_tags().is_empty());
+        // round trip
+        let txc = tx.clone_with_tags(vec!["flat"]);
+        let txr = TxRecord::from_string_record(&txc.to_string_record()).unwrap();
+        assert_eq!(txr.get_tags(), vec!["flat"]);
+        assert_eq!(txc, txr);
+    }
+
+    #[test]
+    fn test_nth_weekday_start() {
+        let on = date(15, 3, 2021);
+        let tests = vec![
+            ("Rent 1000€ 1m 1st-mon", date(1, 3, 2021)),
+            ("Rent 1000€ 1m 1ST-Mon", date(1, 3, 2021)),
+            ("Rent 1000€ 2nd-fri 1m", date(12, 3, 2021)),
+            ("Rent 1000€ 1m 3rd-sun", date(21, 3, 2021)),
+            ("Rent 1000€ 1m 5th-wed", date(31, 3, 2021)),
+        ];
+        for (s, starts_on) in tests {
+            let tx = TxRecord::from_str_on(s, &on).unwrap();
+            assert_eq!(tx.get_starts_on(), starts_on);
+            assert_eq!(tx.get_name(), "Rent");
+            assert_eq!(tx.get_lifetime().to_string(), "1m1x");
+        }
+        // the weekday does not exist in the month
+        let err = TxRecord::from_str_on("Rent 1000€ 5th-thu", &on).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid date format: there is no 5th-thu in March 2021"
+        );
+        // not a weekday
+        let tx = TxRecord::from_str_on("Rent 1000€ 6th-mon 1st-xyz", &on).unwrap();
+        assert_eq!(tx.get_name(), "Rent 6th-mon 1st-xyz");
+        assert_eq!(tx.get_starts_on(), on);
+    }
+
+    #[test]
+    fn test_from_cents() {
+        let on = date(1, 1, 2021);
+        let tx = TxRecord::from_cents("Netflix", 1999, "€", on, Lifetime::SingleDay).unwrap();
+        assert_eq!(tx.get_amount(), parse_amount("19.99").unwrap());
+        assert_eq!(tx.get_currency(), "EUR");
+        assert_eq!(tx.get_starts_on(), on);
+        assert_eq!(tx, TxRecord::from_str("Netflix 19.99€ 010121").unwrap());
+        let tx = TxRecord::from_cents("Ramen", 980, "JPY", on, Lifetime::SingleDay).unwrap();
+        assert_eq!(tx.get_amount(), parse_amount("980").unwrap());
+        assert_eq!(tx.get_currency(), "JPY");
+        // the amount must not be zero, it can be negative
+        assert!(TxRecord::from_cents("Nothing", 0, "€", on, Lifetime::SingleDay).is_err());
+        let tx = TxRecord::from_cents("Refund", -100, "€", on, Lifetime::SingleDay).unwrap();
+        assert_eq!(tx, TxRecord::from_str("Refund -1€ 010121").unwrap());
+        // and within the limits
+        let lifetime = Lifetime::Month {
+            amount: 1,
+            times: 1000,
+        };
+        assert!(TxRecord::from_cents("Big", i64::MAX, "€", on, lifetime).is_err());
+        // round trip
+        let tx = TxRecord::from_cents("Netflix", 799, "€", on, "1m12x".parse().unwrap()).unwrap();
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(tx, txr);
+    }
+
+    #[test]
+    fn test_from_str_on() {
+        let on = date(15, 3, 2020);
+        let tx = TxRecord::from_str_on("Rent 1000€ 1m", &on).unwrap();
+        assert_eq!(tx.get_starts_on(), on);
+        assert_eq!(tx.get_recorded_at().naive_local().date(), on);
+        let tx = TxRecord::from_str_on("Rent 1000€ 1m +30d", &on).unwrap();
+        assert_eq!(tx.get_starts_on(), date(14, 4, 2020));
+        // an explicit date wins
+        let tx = TxRecord::from_str_on("Rent 1000€ 1m 010121", &on).unwrap();
+        assert_eq!(tx.get_starts_on(), date(1, 1, 2021));
+        assert_eq!(tx.get_recorded_at().naive_local().date(), on);
+        // same as from_str for today
+        let tx = TxRecord::from_str_on("Rent 1000€ 1m #home", &today()).unwrap();
+        assert_eq!(tx, TxRecord::from_str("Rent 1000€ 1m #home").unwrap());
+        assert_eq!(tx.get_recorded_at().naive_local().date(), today());
+    }
+
+    #[test]
+    fn test_spec_string() {
+        let tests = vec![
+            "Netflix 7.99€ 1m12x 010321 #movies #covid",
+            "Sushi 3000¥ 020121 #\"eat out\"",
+            "Book 20€ vendor=Amazon #books",
+            "Oil 10.125BHD 2w 311222",
+            "Coffee 2$",
+        ];
+        for s in tests {
+            let tx = TxRecord::from_str(s).unwrap();
+            let spec = tx.to_spec_string();
+            let txr = TxRecord::from_str(&spec).unwrap();
+            assert_eq!(txr.get_name(), tx.get_name());
+            assert_eq!(txr.get_amount(), tx.get_amount());
+            assert_eq!(txr.get_currency(), tx.get_currency());
+            assert_eq!(txr.get_lifetime(), tx.get_lifetime());
+            assert_eq!(txr.get_starts_on(), tx.get_starts_on());
+            assert_eq!(txr.get_tags(), tx.get_tags());
+            assert_eq!(txr.get_metadata(), tx.get_metadata());
+            assert_eq!(tx, txr, "{} -> {}", s, spec);
+        }
+        // the start date is omitted when it is today
+        let tx = TxRecord::from_str("Coffee 2€ #daily").unwrap();
+        assert_eq!(tx.to_spec_string(), "Coffee 2.00€ 1d1x #daily");
+        let tx = TxRecord::from_str("Rent 1000€ 1m12x 010121").unwrap();
+        assert_eq!(tx.to_spec_string(), "Rent 1000.00€ 1m12x 010121");
+    }
+
+    #[test]
+    fn test_share() {
+        let tx = TxRecord::from_str("Spotify 30€ 1m /3 010121").unwrap();
+        assert_eq!(tx.get_name(), "Spotify");
+        assert_eq!(tx.get_amount(), parse_amount("10").unwrap());
+        assert_eq!(tx.get_amount_total(), parse_amount("10").unwrap());
+        assert_eq!(tx.get_meta("full_amount"), Some("30.00"));
+        assert_eq!(tx.per_diem(), parse_amount("0.32").unwrap());
+        let tx = TxRecord::from_str("Netflix 17.99€ 1m12x /2 010121").unwrap();
+        assert_eq!(tx.get_amount(), parse_amount("9").unwrap());
+        assert_eq!(tx.get_amount_total(), parse_amount("108").unwrap());
+        assert_eq!(tx.get_meta("full_amount"), Some("17.99"));
+        assert_eq!(tx.per_diem(), parse_amount("0.30").unwrap());
+        // with_share
+        let tx = TxRecord::from_str("Rent 1000€ 1m 010121").unwrap();
+        let half = parse_amount("0.5").unwrap();
+        let got = tx.with_share(&half).unwrap();
+        assert_eq!(got.get_amount(), parse_amount("500").unwrap());
+        assert_eq!(got.get_meta("full_amount"), Some("1000.00"));
+        assert_eq!(got.per_diem(), parse_amount("16.13").unwrap());
+        assert_eq!(
+            tx.with_share(&BigDecimal::one()).unwrap().get_amount(),
+            tx.get_amount()
+        );
+        // invalid shares
+        assert!(tx.with_share(&BigDecimal::zero()).is_err());
+        assert!(tx.with_share(&parse_amount("1.5").unwrap()).is_err());
+        assert!(TxRecord::from_str("Coffee 0.01€ /3").is_err());
+        let tx = TxRecord::from_str("Rent 1000€ /0").unwrap();
+        assert_eq!(tx.get_name(), "Rent /0");
+        // round trip
+        let tx = TxRecord::from_str("Rent 1000€ 1m 010121").unwrap();
+        let tx = tx.with_share(&half).unwrap();
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(txr.get_amount(), parse_amount("500").unwrap());
+        let tx = TxRecord::from_str("Spotify 30€ 1m /3 010121").unwrap();
+        let txr = TxRecord::from_str(&tx.to_spec_string()).unwrap();
+        assert_eq!(tx, txr);
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(tx, txr);
+    }
+
+    #[test]
+    fn test_category() {
+        let tx = TxRecord::from_str("Rent 1000€ 1m ::Housing #home").unwrap();
+        assert_eq!(tx.get_name(), "Rent");
+        assert_eq!(tx.get_category(), Some("Housing"));
+        assert_eq!(tx.get_tags(), vec!["home"]);
+        // the last one wins
+        let tx = TxRecord::from_str("Bus 2€ ::Transport ::Travel").unwrap();
+        assert_eq!(tx.get_category(), Some("Travel"));
+        // no category
+        let tx = TxRecord::from_str("Rent 1000€ 1m #home").unwrap();
+        assert_eq!(tx.get_category(), None);
+        let tx = TxRecord::from_str("Rent 1000€ :: ::1st").unwrap();
+        assert_eq!(tx.get_category(), None);
+        assert_eq!(tx.get_name(), "Rent :: ::1st");
+        // round trip
+        let mut tx = TxRecord::new("Rent", "1000").unwrap();
+        tx.category = Some("Housing".to_string());
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(txr.get_category(), Some("Housing"));
+        assert_eq!(tx, txr);
+        let txr = TxRecord::from_str(&tx.to_spec_string()).unwrap();
+        assert_eq!(tx, txr);
+        // records without a category are still readable
+        let txr = TxRecord::from_string_record(
+            "2021-01-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m #home",
+        )
+        .unwrap();
+        assert_eq!(txr.get_category(), None);
+        let txr = TxRecord::from_string_record(
+            "2021-01-01T10:00:00+01:00::2021-01-01::Rent 1000€ 1m ::Housing",
+        )
+        .unwrap();
+        assert_eq!(txr.get_category(), Some("Housing"));
+    }
+
+    #[test]
+    fn test_comment() {
+        let tx = TxRecord::from_str("Gym 30€ 1m 010121 #sport // cancelled in March").unwrap();
+        assert_eq!(tx.get_name(), "Gym");
+        assert_eq!(tx.get_tags(), vec!["sport"]);
+        assert_eq!(tx.get_note(), Some("cancelled in March"));
+        let tx = TxRecord::from_str("Gym 30€ 1m //cancelled").unwrap();
+        assert_eq!(tx.get_name(), "Gym");
+        assert_eq!(tx.get_note(), Some("cancelled"));
+        // empty comment
+        let tx = TxRecord::from_str("Gym 30€ 1m //").unwrap();
+        assert_eq!(tx.get_name(), "Gym");
+        assert_eq!(tx.get_note(), None);
+        // the comment is ignored by the parser
+        let tx = TxRecord::from_str("Gym 30€ // 1y #later").unwrap();
+        assert_eq!(tx.get_lifetime(), &Lifetime::SingleDay);
+        assert!(tx.get_tags().is_empty());
+        // urls are not comments
+        let tx = TxRecord::from_str("Gym 30€ url=https://gym.example.com // see url").unwrap();
+        assert_eq!(tx.get_name(), "Gym");
+        assert_eq!(tx.get_meta("url"), Some("https://gym.example.com"));
+        assert_eq!(tx.get_note(), Some("see url"));
+        // records
+        let txr = TxRecord::from_string_record(
+            "2021-01-01T10:00:00+01:00::2021-01-01::Gym 30€ 1m // cancelled in March",
+        )
+        .unwrap();
+        assert_eq!(txr.get_name(), "Gym");
+        assert_eq!(txr.get_note(), Some("cancelled in March"));
+        // round trip
+        let mut tx = TxRecord::new("Gym", "30").unwrap();
+        tx.note = Some("cancelled in March".to_string());
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(txr.get_note(), Some("cancelled in March"));
+        let txr = TxRecord::from_str(&tx.to_spec_string()).unwrap();
+        assert_eq!(txr.get_note(), Some("cancelled in March"));
+        assert_eq!(tx, txr);
+    }
+
+    #[test]
+    fn test_amount_precision() {
+        let tests = vec![
+            ("Coffee 0.5€", "0.5", "0.50"),
+            ("Gas 9.999€ 1w", "9.999", "10.00"),
+            ("Gas 9.994€ 1w", "9.994", "9.99"),
+            ("Rent 20€ 1m", "20", "20.00"),
+            ("Fee 1.2345BHD", "1.2345", "1.235"),
+            ("Ramen 980.5¥", "980.5", "981"),
+            ("Ramen 980.4¥", "980.4", "980"),
+            ("Fee 0.004BHD", "0.004", "0.004"),
+        ];
+        for (spec, amount, rounded) in tests {
+            let tx = TxRecord::from_str(spec).unwrap();
+            assert_eq!(
+                extract_amount(spec.split(' ').nth(1).unwrap()).unwrap().0,
+                amount
+            );
+            assert_eq!(tx.get_amount().to_string(), rounded);
+        }
+        // the full precision is used for the per diem
+        let tx = TxRecord::from_str("Gas 9.999€ 3d").unwrap();
+        assert_eq!(tx.per_diem().to_string(), "3.33");
+        let tx = TxRecord::from_str("Gas 20€ 3d").unwrap();
+        assert_eq!(tx.per_diem().to_string(), "6.67");
+        // the amount is validated in its currency
+        assert!(TxRecord::from_str("Gum 0.4¥").is_err());
+        assert!(TxRecord::from_str("Gum 0.004€").is_err());
+        assert!(TxRecord::from_str("Gum 0.5¥").is_ok());
+        // whole amounts are displayed with the currency decimals
+        let tx = TxRecord::from_str("Rent 100€ 10d").unwrap();
+        assert_eq!(tx.get_amount().to_string(), "100.00");
+        assert_eq!(tx.per_diem().to_string(), "10.00");
+        let tx = TxRecord::from_str("Ramen 1000¥ 10d").unwrap();
+        assert_eq!(tx.get_amount().to_string(), "1000");
+        assert_eq!(tx.per_diem().to_string(), "100");
+    }
+
+    #[test]
+    fn test_amount_plain() {
+        let tx = TxRecord::from_str("Rent 1000€ 1m 010121").unwrap();
+        assert_eq!(tx.get_amount_plain(), "1000.00");
+        let tx = TxRecord::from_str("Ramen 1000¥").unwrap();
+        assert_eq!(tx.get_amount_plain(), "1000");
+        let tx = TxRecord::from_str("Fee 10BHD").unwrap();
+        assert_eq!(tx.get_amount_plain(), "10.000");
+    }
+
+    #[test]
+    fn test_currency() {
+        // default currency
+        let tx = TxRecord::from_str("Rent 1000€ 1m 010121").unwrap();
+        assert_eq!(tx.get_currency(), "EUR");
+        assert_eq!(tx.get_amount().to_string(), "1000.00");
+        assert_eq!(tx.per_diem().to_string(), "32.26");
+        // currency without decimals
+        let tx = TxRecord::from_str("Ramen 1000¥ 1m 010121").unwrap();
+        assert_eq!(tx.get_currency(), "JPY");
+        assert_eq!(tx.get_amount().to_string(), "1000");
+        assert_eq!(tx.per_diem().to_string(), "32");
+        // currency with 3 decimals
+        let tx = TxRecord::from_str("Fee 10BHD 1m 010121").unwrap();
+        assert_eq!(tx.get_currency(), "BHD");
+        assert_eq!(tx.get_amount().to_string(), "10.000");
+        assert_eq!(tx.per_diem().to_string(), "0.323");
+        // the currency survives serialization
+        let txr = TxRecord::from_string_record(&tx.to_string_record()).unwrap();
+        assert_eq!(txr.get_currency(), "BHD");
+        // digits followed by capitals are not an amount, unless a currency
+        let tests = vec![
+            ("Flat 2BED 900€ 1m 010121", "Flat 2BED"),
+            ("iPhone 12PRO 900€ 1y 010121", "iPhone 12PRO"),
+        ];
+        for (spec, name) in tests {
+            let tx = TxRecord::from_str(spec).unwrap();
+            assert_eq!(tx.get_name(), name, "{}", spec);
+            assert_eq!(tx.get_amount().to_string(), "900.00", "{}", spec);
+            assert_eq!(tx.get_currency(), "EUR", "{}", spec);
+        }
+        // the generic currency sign is the default currency
+        let tx = TxRecord::from_str("Rent 1000¤ 1m 010121").unwrap();
+        assert_eq!(tx.get_currency(), DEFAULT_CURRENCY);
+        let tx = TxRecord::from_str_in("Rent 1000¤ 1m 010121", &today(), "JPY").unwrap();
+        assert_eq!(tx.get_currency(), "JPY");
+        assert_eq!(tx.get_amount().to_string(), "1000");
+        let tx = TxRecord::from_str_in("Rent 1000.5¤ 1m 010121", &today(), "£").unwrap();
+        assert_eq!(tx.get_currency(), "GBP");
+        assert!(tx.to_string_record().contains(" 1000.50£ "));
+        // not for the other currencies
+        let tx = TxRecord::from_str_in("Rent 1000€ 1m 010121", &today(), "USD").unwrap();
+        assert_eq!(tx.get_currency(), "EUR");
+        // the amounts with the generic currency sign
+        let tests = vec![
+            ("Rent 1000¤ 1m", true),
+            ("Rent ~20¤/month", true),
+            ("Rent 1000€ 1m", false),
+            ("Rent 1000 1m #¤", false),
+            ("Rent 1000€ 1m // was 900¤", false),
+        ];
+        for (spec, expected) in tests {
+            assert_eq!(has_generic_currency(spec), expected, "{}", spec);
+        }
     }
 
     #[test]
     fn test_costoflife() {
-        let txs = vec![
+        let txs = [
             // insert one entry
             TxRecord::new("Test#1", "10.2311321").unwrap(),
             TxRecord::new("Test#2", "10.5441231").unwrap(),
@@ -1,18 +1,26 @@
-mod ledger;
-use ledger::DataStore;
-
+mod config;
 mod interaction;
+use config::{Config, CONFIG_FILENAME};
+use interaction::PolarAnswer::Yes;
 
 use std::fmt;
 
-use clap::{Arg, Command};
-use dialoguer::{theme::ColorfulTheme, Confirm};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use clap::{Arg, ArgMatches, Command};
+use clap_complete::{generate, Shell};
+use costoflife::ledger::{DataStore, TagFilter};
+use costoflife::{AmountKind, Period, TxRecord};
+use dialoguer::Confirm;
 use directories_next::ProjectDirs;
 use pad::{Alignment, PadStr};
+use serde_json::json;
 
+use std::cmp::{max, min};
 use std::error;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use Alignment::*;
@@ -20,11 +28,19 @@ use Cell::*;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const DB_FILENAME: &str = "costoflife.data.txt";
+/// The max width of the columns of auto sized tables
+const AUTO_MAX_WIDTH: usize = 50;
+/// The max number of occurrences listed for a transaction
+const OCCURRENCES_MAX: usize = 24;
+/// The message of the commands that have nothing to show
+const NO_TRANSACTIONS: &str =
+    "no transactions yet, add one with: costoflife add Car 2000€ .transport 5y";
 
-fn main() -> Result<(), Box<dyn error::Error>> {
-    //println!("Welcome to CostOf.Life!");
-
-    let matches = Command::new("costoflife")
+/// Build the command line interface
+///
+/// It is used both to parse the arguments and to generate the completions
+fn cli() -> Command<'static> {
+    Command::new("costoflife")
         .version(VERSION)
         .author("Andrea G. <no.andrea@gmail.com>")
         .about("keep track of the cost of your daily life")
@@ -34,15 +50,52 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 .short('c')
                 .long("config")
                 .value_name("FILE")
-                .help("Sets a custom config file")
+                .help("use this config file instead of the one in the config dir")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("file")
+                .short('f')
+                .long("file")
+                .value_name("FILE")
+                .help("use this data file, repeat it to query several files together")
+                .multiple_occurrences(true)
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("save_to")
+                .long("save-to")
+                .value_name("FILE")
+                .help("the data file to save to, required to save when using several files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("no_color")
+                .long("no-color")
+                .help("disable colors and unicode glyphs in the output (same as NO_COLOR)"),
+        )
         .arg(
             Arg::new("on_date")
                 .short('o')
                 .long("on")
                 .value_name("DATE")
-                .help("use this date to calculate the cost of life")
+                .help("use this date as today, to calculate the cost of life and to add expenses")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("tag")
+                .long("tag")
+                .value_name("TAG")
+                .help("only count the expenses with this tag in the cost of life and the summary, repeat it for more tags")
+                .multiple_occurrences(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("exclude_tag")
+                .long("exclude-tag")
+                .value_name("TAG")
+                .help("do not count the expenses with this tag in the cost of life and the summary, repeat it for more tags")
+                .multiple_occurrences(true)
                 .takes_value(true),
         )
         .subcommand(
@@ -61,10 +114,213 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .short('y')
                         .takes_value(false)
                         .help("automatically reply yes"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .short('v')
+                        .takes_value(false)
+                        .help("list the occurrences of the expense"),
+                ),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("show the expenses with a name")
+                .arg(
+                    Arg::new("NAME")
+                        .help("the name of the expenses")
+                        .required(true)
+                        .multiple_occurrences(true)
+                        .value_terminator("."),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .short('v')
+                        .takes_value(false)
+                        .help("list the occurrences of the expenses"),
+                ),
+        )
+        .subcommand(
+            Command::new("summary")
+                .about("print th expenses summary")
+                .arg(
+                    Arg::new("limit")
+                        .long("limit")
+                        .visible_alias("top")
+                        .short('n')
+                        .value_name("N")
+                        .help("show only the first N expenses")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("unit")
+                        .long("unit")
+                        .short('u')
+                        .value_name("UNIT")
+                        .possible_values(["day", "week", "month", "year"])
+                        .default_value("day")
+                        .help("show the costs per day, week, month or year")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("trailing")
+                        .long("trailing")
+                        .value_name("DAYS")
+                        .help("show also the average daily cost over the last DAYS days")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("tags")
+                .about("print th expenses tags summary")
+                .arg(
+                    Arg::new("split")
+                        .long("split")
+                        .takes_value(false)
+                        .help("divide each expense equally among its tags"),
+                ),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("print the expenses summary, tags and stats")
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .takes_value(false)
+                        .help("print the report as json"),
+                ),
+        )
+        .subcommand(
+            Command::new("expiring")
+                .about("print the expenses expiring soon")
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .short('d')
+                        .value_name("DAYS")
+                        .default_value("30")
+                        .help("number of days to look ahead")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("print a one line summary, for status bars and prompts")
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .short('d')
+                        .value_name("DAYS")
+                        .default_value("30")
+                        .help("number of days to look ahead for expiring expenses")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("import expenses from a file")
+                .arg(
+                    Arg::new("specs")
+                        .long("specs")
+                        .value_name("FILE")
+                        .help("a json file with a list of expense strings")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list all the expenses, or the ones with an amount within a range")
+                .arg(
+                    Arg::new("min")
+                        .long("min")
+                        .value_name("AMOUNT")
+                        .default_value("0")
+                        .help("the minimum amount, included")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("max")
+                        .long("max")
+                        .value_name("AMOUNT")
+                        .help("the maximum amount, included")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("total")
+                        .long("total")
+                        .takes_value(false)
+                        .help("compare the total amount instead of the amount"),
+                )
+                .arg(
+                    Arg::new("active")
+                        .long("active")
+                        .takes_value(false)
+                        .conflicts_with_all(&["expired", "future"])
+                        .help("only the expenses active on the date"),
+                )
+                .arg(
+                    Arg::new("expired")
+                        .long("expired")
+                        .takes_value(false)
+                        .conflicts_with("future")
+                        .help("only the expenses ended before the date"),
+                )
+                .arg(
+                    Arg::new("future")
+                        .long("future")
+                        .takes_value(false)
+                        .help("only the expenses starting after the date"),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("export the expenses")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .value_name("FORMAT")
+                        .possible_values(["json", "csv", "toml"])
+                        .default_value("json")
+                        .help("the export format")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("the file to write to, defaults to stdout")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("check the data file for issues")
+                .arg(
+                    Arg::new("FILE")
+                        .help("the file to check, defaults to the data file")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("merge the records of the same recurring expenses added more than once"),
+        )
+        .subcommand(Command::new("menu").about("start the interactive menu"))
+        .subcommand(
+            Command::new("completions")
+                .about("print the completion script for a shell")
+                .arg(
+                    Arg::new("shell")
+                        .possible_values(["bash", "zsh", "fish"])
+                        .help("the shell to generate the script for")
+                        .required(true)
+                        .takes_value(true),
                 ),
         )
-        .subcommand(Command::new("summary").about("print th expenses summary"))
-        .subcommand(Command::new("tags").about("print th expenses tags summary"))
         .subcommand(
             Command::new("search")
                 .about("search for a transaction")
@@ -76,72 +332,132 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .value_terminator("."),
                 ),
         )
-        .get_matches();
-
-    // first, see if there is the config dir
-    let path = match ProjectDirs::from("com", "FarcastTo", "CostOf.Life") {
-        Some(p) => {
-            if !p.data_dir().exists() {
-                let authorized = Confirm::with_theme(&ColorfulTheme::default())
-                    .with_prompt("The CostOf.Life data dir does not exists, can I create it?")
-                    .default(true)
-                    .interact()
-                    .unwrap();
-                if !authorized {
-                    println!("nevermind then :(");
-                    return Ok(());
-                }
-                match fs::create_dir_all(p.data_dir()) {
-                    Ok(_) => println!("data folder created at {:?}", p.data_dir()),
-                    Err(e) => {
-                        println!("error creating folder {:?}: {}", p.data_dir(), e);
-                        panic!()
+}
+
+fn main() -> Result<(), Box<dyn error::Error>> {
+    //println!("Welcome to CostOf.Life!");
+
+    let matches = cli().get_matches();
+
+    // plain output for dumb terminals and logs
+    let plain = matches.is_present("no_color")
+        || matches!(std::env::var_os("NO_COLOR"), Some(v) if !v.is_empty());
+    interaction::set_plain(plain);
+    // the completions do not need the data
+    if let Some(("completions", c)) = matches.subcommand() {
+        let shell = c
+            .value_of("shell")
+            .and_then(|v| Shell::from_str(v).ok())
+            .expect("The shell provided is not supported");
+        generate(shell, &mut cli(), "costoflife", &mut io::stdout());
+        return Ok(());
+    }
+    // the config, from the flag or from the config dir, if any
+    let config = match matches.value_of("config") {
+        Some(file) => Config::load(Path::new(file)),
+        None => ProjectDirs::from("com", "FarcastTo", "CostOf.Life")
+            .map(|p| p.config_dir().join(CONFIG_FILENAME))
+            .filter(|p| p.exists())
+            .map_or_else(|| Ok(Config::default()), |p| Config::load(&p)),
+    };
+    let config = config.unwrap_or_else(|e| {
+        eprintln!("cannot read the config: {}", e);
+        std::process::exit(1)
+    });
+    config::set(config.clone());
+    // the data files, the one of the config or the default one if none is set
+    let files = match (matches.values_of("file"), config.data_file) {
+        (Some(values), _) => values.map(PathBuf::from).collect::<Vec<PathBuf>>(),
+        (None, Some(file)) => vec![file],
+        (None, None) => {
+            // first, see if there is the config dir
+            let path = match ProjectDirs::from("com", "FarcastTo", "CostOf.Life") {
+                Some(p) => {
+                    if !p.data_dir().exists() {
+                        // in scripts there is nobody to ask, go ahead and create it
+                        let authorized = !interaction::is_interactive()
+                            || Confirm::with_theme(&*interaction::theme())
+                                .with_prompt(
+                                    "The CostOf.Life data dir does not exists, can I create it?",
+                                )
+                                .default(true)
+                                .interact()
+                                .unwrap();
+                        if !authorized {
+                            println!("nevermind then :(");
+                            return Ok(());
+                        }
+                        match fs::create_dir_all(p.data_dir()) {
+                            Ok(_) => println!("data folder created at {:?}", p.data_dir()),
+                            Err(e) => {
+                                eprintln!("error creating folder {:?}: {}", p.data_dir(), e);
+                                std::process::exit(1)
+                            }
+                        }
                     }
+                    p.data_dir().join(Path::new(DB_FILENAME))
                 }
-            }
-            p.data_dir().join(Path::new(DB_FILENAME))
+                None => panic!("cannot retrieve the config file dir"),
+            };
+            vec![path]
         }
-        None => panic!("cannot retrieve the config file dir"),
+    };
+    // with several data files the one to save to must be explicit
+    let path = match (matches.value_of("save_to"), files.as_slice()) {
+        (Some(target), _) => Some(PathBuf::from(target)),
+        (None, [file]) => Some(file.clone()),
+        (None, _) => None,
     };
     // load the datastores
-    let mut ds = DataStore::new();
-    ds.load(path.as_path())?;
+    let mut ds = DataStore::with_cache();
+    ds.load_many(&files.iter().map(|f| f.as_path()).collect::<Vec<&Path>>())?;
     // get the date
     let target_date = match matches.value_of("on_date") {
         Some(v) => costoflife::date_from_str(v).expect("The date provided is not valid"),
         None => costoflife::today(),
     };
+    // the tags to count in the cost of life and the summary
+    let tags_of = |id: &str| {
+        matches
+            .values_of(id)
+            .map_or(Vec::new(), |values| values.collect::<Vec<&str>>())
+    };
+    let filter = TagFilter::new(&tags_of("tag"), &tags_of("exclude_tag"));
+    // the period to show the costs over
+    let mut unit = Period::Day;
+    if ds.size(None) == 0 && needs_transactions(&matches) {
+        println!("{}", NO_TRANSACTIONS);
+        return Ok(());
+    }
     // command line
     match matches.subcommand() {
         Some(("add", c)) => {
             if let Some(values) = c.values_of("EXP_STR") {
                 let v = values.collect::<Vec<&str>>().join(" ");
-                let tx = costoflife::TxRecord::from_str(&v).expect("Cannot parse the input string");
+                let currency = config::get().currency;
+                interaction::warn_generic_currency(&v, &currency);
                 // check the values for
                 if c.is_present("non_interactive") {
-                    ds.insert(&tx);
-                    ds.save(path.as_path())?;
+                    ds.insert_str_in(&v, &target_date, &currency)
+                        .expect("Cannot parse the input string");
+                    save(&ds, &path)?;
                     println!("done!");
                     return Ok(());
                 }
+                let tx = ds
+                    .parse_str_in(&v, &target_date, &currency)
+                    .expect("Cannot parse the input string");
                 // print the transaction
-                println!("Name     : {}", tx.get_name());
-                println!("Tags     : {}", tx.get_tags().join(", "));
-                print!("Amount   : {}", tx.get_amount());
-                if !tx.amount_is_total() {
-                    print!("(Total: {}€)", tx.get_amount_total());
-                }
-                println!("\nFrom - To: {} - {}", tx.get_starts_on(), tx.get_ends_on());
-                println!("Per Diem : {}", tx.per_diem());
+                describe(&tx, &target_date, c.is_present("verbose"), plain);
                 // save to the store
-                match Confirm::with_theme(&ColorfulTheme::default())
+                match Confirm::with_theme(&*interaction::theme())
                     .with_prompt("Do you want to add it?")
                     .default(true)
                     .interact()
                 {
                     Ok(true) => {
                         ds.insert(&tx);
-                        ds.save(path.as_path())?;
+                        save(&ds, &path)?;
                         println!("done!")
                     }
                     _ => println!("ok, another time"),
@@ -150,51 +466,352 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 println!("Tell me what to add, eg: Car 2000€ .transport 5y")
             }
         }
-        Some(("summary", _c)) => {
-            let mut p = Printer::new(vec![27, 12, 9, 100]);
+        Some(("show", c)) => {
+            if let Some(values) = c.values_of("NAME") {
+                let name = values.collect::<Vec<&str>>().join(" ");
+                let txs = ds.find_by_name(&name);
+                if txs.is_empty() {
+                    println!("No expenses named {} ¯\\_(ツ)_/¯", name);
+                    return Ok(());
+                }
+                txs.iter().enumerate().for_each(|(i, tx)| {
+                    if i > 0 {
+                        println!();
+                    }
+                    describe(tx, &target_date, c.is_present("verbose"), plain);
+                });
+            }
+        }
+        Some(("summary", c)) => {
+            let limit = c
+                .value_of("limit")
+                .map(|v| v.parse::<usize>().expect("The limit provided is not valid"));
+            unit = c
+                .value_of("unit")
+                .map(|v| v.parse::<Period>().expect("The unit provided is not valid"))
+                .unwrap_or(Period::Day);
+            summary_table(&ds, &target_date, limit, &unit, &filter)
+                .ascii(plain)
+                .render();
+            if let Some(days) = c.value_of("trailing") {
+                let days = days
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|d| *d > 0)
+                    .expect("The number of days provided is not valid");
+                let currency = config::get().currency;
+                println!(
+                    "Trailing {} days CostOf.Life is: {} per day ({} in total)",
+                    days,
+                    fmt_amount(costoflife::to_f32_safe(&ds.trailing_average_daily(
+                        &target_date,
+                        days,
+                        &currency
+                    ))),
+                    fmt_amount(costoflife::to_f32_safe(&ds.trailing_cost(
+                        &target_date,
+                        days,
+                        &currency
+                    )))
+                );
+            }
+            // the cost of life below is always computed on all the (filtered) expenses
+            if limit.is_some() {
+                println!(
+                    "showing {} of {} active expenses",
+                    ds.summary_filtered(&target_date, limit, &unit, &filter)
+                        .len(),
+                    ds.summary_filtered(&target_date, None, &unit, &filter)
+                        .len()
+                );
+            }
+        }
+        Some(("tags", c)) => {
+            tags_table(&ds, &target_date, c.is_present("split"))
+                .ascii(plain)
+                .render();
+        }
+        Some(("report", c)) => {
+            // total per diem
+            let total = ds.cost_of_life(&target_date);
+            let tags = ds.tags(&target_date);
+            let expiring = ds.expiring_within(&target_date, 30);
+            if c.is_present("json") {
+                let report = json!({
+                    "date": target_date.to_string(),
+                    "cost_of_life": total,
+                    "active": ds.size(Some(target_date)),
+                    "top_tag": tags.first().map(|(tag, _, _)| tag),
+                    "expiring": expiring
+                        .iter()
+                        .map(|tx| {
+                            json!({
+                                "name": tx.get_name(),
+                                "ends_on": tx.get_ends_on().to_string(),
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                    "summary": ds
+                        .summary(&target_date, None)
+                        .iter()
+                        .map(|(itm, total, per_diem, prog)| {
+                            json!({
+                                "name": itm,
+                                "total": total,
+                                "per_diem": per_diem,
+                                "progress": prog,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                    "tags": tags
+                        .iter()
+                        .map(|(tag, count, cost)| {
+                            json!({
+                                "tag": tag,
+                                "count": count,
+                                "per_diem": cost,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                });
+                println!("{}", report);
+                return Ok(());
+            }
+            // tables
+            summary_table(&ds, &target_date, None, &Period::Day, &TagFilter::default())
+                .ascii(plain)
+                .render();
+            println!();
+            tags_table(&ds, &target_date, false).ascii(plain).render();
+            println!();
+            // stats
+            println!("Active   : {}", ds.size(Some(target_date)));
+            if let Some((tag, _, cost)) = tags.first() {
+                println!("Top tag  : {} ({})", tag, fmt_amount(*cost));
+            }
+            println!("Expiring : {} in the next 30 days", expiring.len());
+            expiring.iter().for_each(|tx| {
+                println!(
+                    "           {} on {}",
+                    tx.get_name(),
+                    fmt_date(&tx.get_ends_on())
+                );
+            });
+        }
+        Some(("expiring", c)) => {
+            let days = c
+                .value_of("days")
+                .and_then(|v| v.parse::<i64>().ok())
+                .expect("The number of days provided is not valid");
+            let mut p = Printer::new(vec![27, 12, 9, 11, 100]).ascii(plain);
             // title
-            p.head(vec!["Item", "Price", "Diem", "Progress"]);
+            p.head(vec!["Item", "Price", "Diem", "End", "Progress"]);
             p.sep();
-
             // data
-            ds.summary(&target_date)
+            ds.expiring_within(&target_date, days)
                 .iter()
-                .for_each(|(itm, total, per_diem, prog)| {
-                    // ⧚ ░ ◼ ▪ this are characters that can be used for the bar
+                .for_each(|tx| {
                     p.row(vec![
-                        Str(itm.to_string()),
-                        Amt(*total),
-                        Amt(*per_diem),
-                        Pcent(*prog), // completion percentage
+                        Str(tx.get_name().to_string()),
+                        Amt(costoflife::to_f32_safe(&tx.get_amount_total())),
+                        Amt(costoflife::to_f32_safe(&tx.per_diem())),
+                        Str(fmt_date(&tx.get_ends_on())),
+                        Pcent(tx.get_progress(Some(target_date))),
                     ]);
                 });
             // separator
             p.sep();
             p.render();
         }
-        Some(("tags", _c)) => {
-            let mut p = Printer::new(vec![27, 12, 9, 100]);
-
-            p.head(vec!["Title", "Count", "Diem", "%"]);
+        Some(("status", c)) => {
+            let days = c
+                .value_of("days")
+                .and_then(|v| v.parse::<i64>().ok())
+                .expect("The number of days provided is not valid");
+            println!("{}", status_line(&ds, &target_date, days));
+            return Ok(());
+        }
+        Some(("import", c)) => {
+            if let Some(file) = c.value_of("specs") {
+                let json = fs::read_to_string(file)?;
+                let n = ds.import_json_specs(&json)?;
+                save(&ds, &path)?;
+                println!("imported {} expenses", n);
+            }
+        }
+        Some(("list", c)) => {
+            let amount = |arg: &str| {
+                c.value_of(arg)
+                    .map(|v| costoflife::parse_amount(v).expect("The amount provided is not valid"))
+            };
+            let min = amount("min").unwrap_or_else(BigDecimal::zero);
+            let max = amount("max").unwrap_or_else(|| BigDecimal::from(i64::MAX));
+            // sorted by amount within a range, by start date otherwise
+            let txs =
+                match c.occurrences_of("min") > 0 || c.is_present("max") || c.is_present("total") {
+                    true => ds.between_amounts(&min, &max, c.is_present("total")),
+                    false => ds.all(),
+                };
+            // the state of the expenses on the target date
+            let is_expired = |tx: &TxRecord| tx.get_ends_on_at(&target_date) < target_date;
+            let is_future = |tx: &TxRecord| tx.get_starts_on() > target_date;
+            let mut p = Printer::auto().ascii(plain);
+            p.head(vec!["Item", "Price", "Total", "Start", "End", "Active"]);
             p.sep();
-
-            // total per diem
-            let total = ds.cost_of_life(&target_date);
-            // data
-            ds.tags(&target_date).iter().for_each(|(tag, count, cost)| {
-                p.row(vec![
-                    Str(tag.to_string()),
-                    Cnt(*count),
-                    Amt(*cost),
-                    Pcent(cost / total), // tag amount over total
-                ]);
-            });
-            // separator
+            txs.iter()
+                .filter(|tx| match () {
+                    _ if c.is_present("active") => tx.is_active_on(&target_date),
+                    _ if c.is_present("expired") => is_expired(tx),
+                    _ if c.is_present("future") => is_future(tx),
+                    _ => true,
+                })
+                .for_each(|tx| {
+                    let active = match tx.is_active_on(&target_date) {
+                        true => "yes",
+                        false => "no",
+                    };
+                    p.row(vec![
+                        Str(tx.get_name().to_string()),
+                        Amt(costoflife::to_f32_safe(&tx.get_amount())),
+                        Amt(costoflife::to_f32_safe(
+                            &tx.get_amount_total_at(&target_date),
+                        )),
+                        Str(fmt_date(&tx.get_starts_on())),
+                        Str(fmt_date(&tx.get_ends_on_at(&target_date))),
+                        Str(active.to_string()),
+                    ]);
+                });
             p.sep();
             p.render();
         }
+        Some(("export", c)) => {
+            let data = match c.value_of("format") {
+                Some("csv") => Ok(ds.to_csv()),
+                Some("toml") => ds.to_toml(),
+                Some("json") | None => ds.to_json().map(|j| format!("{}\n", j)),
+                Some(f) => {
+                    eprintln!("unknown export format: {}", f);
+                    std::process::exit(1)
+                }
+            };
+            let data = data.unwrap_or_else(|e| {
+                eprintln!("cannot export the expenses: {}", e);
+                std::process::exit(1)
+            });
+            match c.value_of("output") {
+                Some(file) => {
+                    if let Err(e) = fs::write(file, data) {
+                        eprintln!("cannot write to {}: {}", file, e);
+                        std::process::exit(1)
+                    }
+                }
+                None => print!("{}", data),
+            }
+            return Ok(());
+        }
+        Some(("lint", c)) => {
+            let files = c
+                .value_of("FILE")
+                .map_or(files.clone(), |f| vec![PathBuf::from(f)]);
+            let mut found = 0;
+            for file in files {
+                // the data file is created on the first save
+                let data = match c.is_present("FILE") || file.exists() {
+                    true => fs::read_to_string(&file)?,
+                    false => String::new(),
+                };
+                let issues = DataStore::lint_str(&data);
+                if issues.is_empty() {
+                    println!("no issues found in {:?}", file);
+                    continue;
+                }
+                issues.iter().for_each(|i| println!("{}", i));
+                println!("{} issues found in {:?}", issues.len(), file);
+                found += issues.len();
+            }
+            if found > 0 {
+                std::process::exit(1)
+            }
+            return Ok(());
+        }
+        Some(("merge", _c)) => {
+            let merges = ds.merge_same();
+            if merges.is_empty() {
+                println!("nothing to merge");
+            } else {
+                merges.iter().for_each(|(merged, tx)| {
+                    println!(
+                        "merged {} records of {} into: {}",
+                        merged.len(),
+                        tx.get_name(),
+                        tx.to_spec_string()
+                    );
+                });
+                save(&ds, &path)?;
+            }
+        }
+        Some(("menu", _c)) => {
+            while let Some(choice) = interaction::menu() {
+                match choice.as_str() {
+                    "summary" | "tags" if ds.size(None) == 0 => println!("{}", NO_TRANSACTIONS),
+                    "summary" => {
+                        summary_table(&ds, &target_date, None, &Period::Day, &TagFilter::default())
+                            .ascii(plain)
+                            .render()
+                    }
+                    "tags" => tags_table(&ds, &target_date, false).ascii(plain).render(),
+                    "new" => match interaction::new_tx(&ds) {
+                        Ok(tx) => {
+                            ds.insert(&tx);
+                            save(&ds, &path)?;
+                            println!("done!")
+                        }
+                        Err(e) => println!("cannot add the transaction: {}", e),
+                    },
+                    "edit" => {
+                        // pick the record to edit
+                        let records = ds.list();
+                        let labels = records
+                            .iter()
+                            .map(|(_, tx)| {
+                                format!(
+                                    "{} {}{} {}",
+                                    tx.get_name(),
+                                    tx.get_amount(),
+                                    costoflife::currency_symbol(tx.get_currency()),
+                                    tx.get_starts_on()
+                                )
+                            })
+                            .collect::<Vec<String>>();
+                        let selected = interaction::select_opt(
+                            "which one? esc/q to go back",
+                            labels
+                                .iter()
+                                .zip(records.iter())
+                                .map(|(l, (h, _))| (l.as_str(), h))
+                                .collect(),
+                        )
+                        .and_then(|h| records.iter().find(|(x, _)| x == h))
+                        .map(|(h, tx)| (*h, (*tx).clone()));
+                        if let Some((h, tx)) = selected {
+                            match interaction::edit_tx(&ds, &tx) {
+                                Ok(edited) => {
+                                    if interaction::confirm("Do you want to save it?", Yes) == Yes {
+                                        ds.update(&h, &edited);
+                                        save(&ds, &path)?;
+                                        println!("done!")
+                                    }
+                                }
+                                Err(e) => println!("cannot edit the transaction: {}", e),
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
         Some(("search", c)) => {
-            let mut p = Printer::new(vec![40, 12, 8, 11, 11, 30, 40]);
+            let mut p = Printer::auto().ascii(plain);
 
             if let Some(values) = c.values_of("SEARCH_PATTERN") {
                 let pattern = values.collect::<Vec<&str>>().join(" ");
@@ -216,8 +833,8 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                             Str(itm.to_string()),
                             Amt(*price),
                             Amt(*diem),
-                            Str(s.to_string()),
-                            Str(e.to_string()),
+                            Str(fmt_date_str(s)),
+                            Str(fmt_date_str(e)),
                             Str(tags.to_string()),
                             Pcent(*pcent),
                         ]);
@@ -240,10 +857,198 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         }
         Some((&_, _)) | None => {}
     }
-    println!("Today CostOf.Life is: {}€", ds.cost_of_life(&target_date));
+    // the tag filter is applied to the expenses active on the target date
+    let filtered = match filter.is_empty() {
+        true => String::new(),
+        false => format!(" for {}", filter),
+    };
+    match unit {
+        Period::Day if filter.is_empty() => println!(
+            "Today CostOf.Life is: {}",
+            fmt_amount(ds.cost_of_life(&target_date))
+        ),
+        Period::Day => println!(
+            "Today CostOf.Life is: {}{}",
+            fmt_amount(ds.cost_of_life_filtered(&target_date, &unit, &filter)),
+            filtered
+        ),
+        _ => println!(
+            "Today CostOf.Life is: {} per {}{}",
+            fmt_amount(ds.cost_of_life_filtered(&target_date, &unit, &filter)),
+            unit,
+            filtered
+        ),
+    }
     Ok(())
 }
 
+/// Tells if a command has nothing to show without transactions
+///
+/// The commands that add, import or export the transactions,
+/// the status line and the json report do not need them
+fn needs_transactions(matches: &ArgMatches) -> bool {
+    match matches.subcommand() {
+        None => true,
+        Some(("report", c)) => !c.is_present("json"),
+        Some((name, _)) => matches!(
+            name,
+            "show" | "summary" | "tags" | "expiring" | "list" | "merge" | "search"
+        ),
+    }
+}
+
+/// Print the details of a transaction
+///
+/// The lifetimes until cancelled are described as of the date `on`,
+/// with `verbose` the occurrences of the transaction are listed too
+fn describe(tx: &TxRecord, on: &NaiveDate, verbose: bool, plain: bool) {
+    println!("Name     : {}", tx.get_name());
+    println!("Tags     : {}", tx.get_tags().join(", "));
+    let symbol = costoflife::currency_symbol(tx.get_currency());
+    print!("Amount   : {}{}", tx.get_amount(), symbol);
+    if tx.amount_kind() == AmountKind::PerPeriod {
+        print!("(Total: {}{})", tx.get_amount_total_at(on), symbol);
+    }
+    println!(
+        "\nFrom - To: {} - {}",
+        fmt_date(&tx.get_starts_on()),
+        fmt_date(&tx.get_ends_on_at(on))
+    );
+    println!("Per Diem : {}", tx.per_diem_at(on));
+    if verbose {
+        occurrences_table(tx, on, OCCURRENCES_MAX)
+            .ascii(plain)
+            .render();
+    }
+}
+
+/// Build the table of the occurrences of a transaction
+///
+/// Only the first `max` occurrences are listed, followed by
+/// a line with the number of the ones left out, the lifetimes
+/// until cancelled are repeated up to the date `on`
+fn occurrences_table(tx: &TxRecord, on: &NaiveDate, max: usize) -> Printer {
+    let mut p = Printer::auto();
+    p.head(vec!["#", "Date", "Amount"]);
+    p.sep();
+    tx.occurrences_at(on)
+        .take(max)
+        .enumerate()
+        .for_each(|(i, (d, amount))| {
+            p.row(vec![
+                Cnt(i + 1),
+                Str(fmt_date(&d)),
+                Amt(costoflife::to_f32_safe(&amount)),
+            ]);
+        });
+    let repeats = tx.occurrences_at(on).count();
+    if repeats > max {
+        p.row(vec![Empty, Str(format!("… {} more", repeats - max)), Empty]);
+    }
+    p.sep();
+    p
+}
+
+/// Save the datastore to the data file to save to
+///
+/// With several data files there is nothing to save to unless
+/// it is set with `--save-to`, in that case it exits with an error
+fn save(ds: &DataStore, path: &Option<PathBuf>) -> Result<(), Box<dyn error::Error>> {
+    match path {
+        Some(p) => Ok(ds.save(p)?),
+        None => {
+            eprintln!("several data files are used, set the one to save to with --save-to");
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Build the table of the active expenses
+///
+/// The table shows at most `limit` expenses that pass the
+/// tag `filter`, if set, with their cost over the `unit` period
+fn summary_table(
+    ds: &DataStore,
+    d: &NaiveDate,
+    limit: Option<usize>,
+    unit: &Period,
+    filter: &TagFilter,
+) -> Printer {
+    let mut p = Printer::auto();
+    // title
+    let cost = match unit {
+        Period::Day => "Diem",
+        Period::Week => "Week",
+        Period::Month => "Month",
+        Period::Year => "Year",
+    };
+    p.head(vec!["Item", "Price", cost, "Progress"]);
+    p.sep();
+
+    // data
+    ds.summary_filtered(d, limit, unit, filter)
+        .iter()
+        .for_each(|(itm, total, per_diem, prog)| {
+            // ⧚ ░ ◼ ▪ this are characters that can be used for the bar
+            p.row(vec![
+                Str(itm.to_string()),
+                Amt(*total),
+                Amt(*per_diem),
+                Pcent(*prog), // completion percentage
+            ]);
+        });
+    // separator
+    p.sep();
+    p
+}
+
+/// Build the table of the tags of the active expenses
+///
+/// With `split` the expenses are divided among their tags, so the
+/// percentages add up to the tagged expenses, otherwise each tag
+/// counts the full expense
+fn tags_table(ds: &DataStore, d: &NaiveDate, split: bool) -> Printer {
+    let mut p = Printer::new(vec![27, 12, 9, 100]);
+
+    p.head(vec!["Title", "Count", "Diem", "%"]);
+    p.sep();
+
+    // total per diem, it rounds to zero for tiny amounts
+    let total = ds.cost_of_life(d);
+    let share = |cost: f32| match total == 0.0 {
+        true => 0.0,
+        false => cost / total,
+    };
+    // data
+    let tags = match split {
+        true => ds.tags_split(d),
+        false => ds.tags(d),
+    };
+    tags.iter().for_each(|(tag, count, cost)| {
+        p.row(vec![
+            Str(tag.to_string()),
+            Cnt(*count),
+            Amt(*cost),
+            Pcent(share(*cost)), // tag amount over total
+        ]);
+    });
+    // separator
+    p.sep();
+    p
+}
+
+/// Build the one line status of the expenses
+///
+/// The format is stable, eg. `CoL: 92.40€ | 7 active | 2 expiring`
+fn status_line(ds: &DataStore, d: &NaiveDate, days: i64) -> String {
+    format!(
+        "CoL: {} | {} active | {} expiring",
+        fmt_amount(ds.cost_of_life(d)),
+        ds.size(Some(*d)),
+        ds.expiring_within(d, days).len()
+    )
+}
+
 #[derive(Debug)]
 enum Cell {
     Amt(f32),    // amount
@@ -257,6 +1062,7 @@ enum Cell {
 #[derive(Debug)]
 struct Printer {
     sizes: Vec<usize>,
+    auto: bool,
     data: Vec<Vec<Cell>>,
     col_sep: String,
     row_sep: char,
@@ -265,6 +1071,7 @@ struct Printer {
 
 impl fmt::Display for Printer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sizes = self.col_sizes();
         write!(
             f,
             "{}",
@@ -274,14 +1081,18 @@ impl fmt::Display for Printer {
                     row.iter()
                         .enumerate()
                         .map(|(i, c)| {
-                            let s = self.sizes[i];
+                            let s = sizes[i];
                             match c {
                                 Str(v) => v.pad(s, ' ', Left, true),
-                                Amt(v) => format!("{}€", v).pad(s, ' ', Right, false),
+                                Amt(v) => fmt_amount(*v).pad(s, ' ', Right, false),
                                 Cnt(v) => format!("{}", v).pad(s, ' ', Right, false),
                                 Empty => "".pad(s, ' ', Right, false),
                                 Pcent(v) => {
-                                    let p = v * 100.0;
+                                    // no bar for the invalid percentages
+                                    let p = match v.is_finite() {
+                                        true => v * 100.0,
+                                        false => 0.0,
+                                    };
                                     let b = (p as usize * s) / 100; // bar length
                                     format!("{:.2}", p).pad(b, self.progress, Right, false)
                                 }
@@ -297,17 +1108,85 @@ impl fmt::Display for Printer {
     }
 }
 
+/// Format an amount as it is printed in the tables
+fn fmt_amount(v: f32) -> String {
+    let config = config::get();
+    format!("{:.*}{}", config.scale, v, config.currency_symbol())
+}
+
+/// Format a date with the date format of the config
+fn fmt_date(d: &NaiveDate) -> String {
+    d.format(&config::get().date_format).to_string()
+}
+
+/// Format a date written as `YYYY-MM-DD` with the date format
+/// of the config, any other value is returned as it is
+fn fmt_date_str(d: &str) -> String {
+    NaiveDate::from_str(d).map_or_else(|_| d.to_string(), |d| fmt_date(&d))
+}
+
 impl Printer {
     pub fn new(col_sizes: Vec<usize>) -> Printer {
         Printer {
             sizes: col_sizes,
+            auto: false,
             data: Vec::new(),
             row_sep: '-',
-            progress: '▮',
+            progress: config::get().progress,
             col_sep: "|".to_string(),
         }
     }
 
+    /// Size the columns to fit their content, up to a max width
+    ///
+    /// The progress bars always take the max width
+    pub fn auto() -> Printer {
+        Printer {
+            auto: true,
+            ..Printer::new(Vec::new())
+        }
+    }
+
+    /// Get the width of the columns, computed on the content in auto mode
+    fn col_sizes(&self) -> Vec<usize> {
+        if !self.auto {
+            return self.sizes.clone();
+        }
+        let mut sizes = vec![0; self.col_count()];
+        self.data.iter().for_each(|row| {
+            row.iter().enumerate().for_each(|(i, c)| {
+                let w = match c {
+                    Str(v) => v.chars().count(),
+                    Amt(v) => fmt_amount(*v).chars().count(),
+                    Cnt(v) => v.to_string().len(),
+                    Pcent(_) => AUTO_MAX_WIDTH,
+                    Empty | Sep => 0,
+                };
+                sizes[i] = max(sizes[i], min(w, AUTO_MAX_WIDTH));
+            })
+        });
+        sizes
+    }
+
+    /// Get the number of columns
+    fn col_count(&self) -> usize {
+        self.data
+            .iter()
+            .map(|row| row.len())
+            .chain(std::iter::once(self.sizes.len()))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Use only ascii glyphs for the progress bars
+    /// (the separators are always ascii)
+    pub fn ascii(mut self, ascii: bool) -> Printer {
+        if ascii {
+            self.progress = '#';
+        }
+        self
+    }
+
     pub fn row(&mut self, row_data: Vec<Cell>) {
         self.data.push(row_data);
     }
@@ -317,7 +1196,7 @@ impl Printer {
     }
 
     pub fn sep(&mut self) {
-        self.row(self.sizes.iter().map(|_| Sep).collect());
+        self.row((0..self.col_count()).map(|_| Sep).collect());
     }
 
     pub fn render(&self) {
@@ -344,25 +1223,170 @@ mod tests {
             Str("Two".to_string()),
             Amt(59.0),
             Cnt(321),
-            Pcent(0.0420123123), // completion percentage
+            Pcent(0.042_012_31), // completion percentage
         ]);
         p.row(vec![
             Str("Three".to_string()),
             Amt(220.0),
             Cnt(11),
-            Pcent(0.309312321), // completion percentage
+            Pcent(0.309_312_3), // completion percentage
         ]);
         p.sep();
 
         let printed =
             "a    |b         |c         |d                                                 
 -----|----------|----------|--------------------------------------------------
-One  |       80€|       100|10.43
-Two  |       59€|       321|4.20
-Three|      220€|        11|▮▮▮▮▮▮▮▮▮▮30.93
+One  |    80.00€|       100|10.43
+Two  |    59.00€|       321|4.20
+Three|   220.00€|        11|▮▮▮▮▮▮▮▮▮▮30.93
 -----|----------|----------|--------------------------------------------------";
 
         assert_eq!(p.data.len(), 6);
         assert_eq!(p.to_string(), printed);
+        // ascii only
+        let p = p.ascii(true);
+        assert!(!p.to_string().contains('▮'));
+        assert!(p.to_string().contains("|##########30.93"));
+        // auto sized
+        let mut p = Printer::auto();
+        p.head(vec!["Item", "Price", "%"]);
+        p.sep();
+        p.row(vec![Str("One".to_string()), Amt(80.0), Pcent(0.5)]);
+        p.row(vec![
+            Str("A very long transaction name that does not fit in the column".to_string()),
+            Amt(1220.5),
+            Pcent(0.25),
+        ]);
+        p.row(vec![Empty, Cnt(3), Empty]);
+        p.sep();
+        assert_eq!(p.col_sizes(), vec![AUTO_MAX_WIDTH, 8, AUTO_MAX_WIDTH]);
+        let printed = "Item                                              |Price   |%                                                 
+--------------------------------------------------|--------|--------------------------------------------------
+One                                               |  80.00€|####################50.00
+A very long transaction name that does not fit in |1220.50€|#######25.00
+                                                  |       3|                                                  
+--------------------------------------------------|--------|--------------------------------------------------";
+        assert_eq!(p.ascii(true).to_string(), printed);
+        // empty
+        let p = Printer::auto();
+        assert!(p.col_sizes().is_empty());
+        assert_eq!(p.to_string(), "");
+    }
+
+    #[test]
+    fn test_completions() {
+        cli().debug_assert();
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let mut script = Vec::new();
+            generate(shell, &mut cli(), "costoflife", &mut script);
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("completions"), "{}", shell);
+            assert!(script.contains("summary"), "{}", shell);
+        }
+    }
+
+    #[test]
+    fn test_occurrences_table() {
+        let tx = TxRecord::from_str("Rent 1000€ 1m12x 010121").unwrap();
+        let d = NaiveDate::from_ymd(2021, 1, 10);
+        let printed = "#|Date      |Amount  
+-|----------|--------
+1|2021-01-01|1000.00€
+2|2021-02-01|1000.00€
+3|2021-03-01|1000.00€
+ |… 9 more  |        
+-|----------|--------";
+        assert_eq!(occurrences_table(&tx, &d, 3).to_string(), printed);
+        let p = occurrences_table(&tx, &d, 24);
+        assert_eq!(p.data.len(), 12 + 3);
+        assert!(!p.to_string().contains("more"));
+    }
+
+    #[test]
+    fn test_empty_store() {
+        let ds = DataStore::new();
+        let d = NaiveDate::from_ymd(2021, 1, 10);
+        // the commands with nothing to show
+        let tests = vec![
+            (vec![], true),
+            (vec!["-o", "100121"], true),
+            (vec!["--tag", "home"], true),
+            (vec!["show", "Rent"], true),
+            (vec!["summary"], true),
+            (vec!["summary", "--trailing", "30"], true),
+            (vec!["tags", "--split"], true),
+            (vec!["report"], true),
+            (vec!["report", "--json"], false),
+            (vec!["expiring"], true),
+            (vec!["status"], false),
+            (vec!["import", "--specs", "specs.json"], false),
+            (vec!["list", "--active"], true),
+            (vec!["export"], false),
+            (vec!["lint"], false),
+            (vec!["merge"], true),
+            (vec!["menu"], false),
+            (vec!["search", "rent"], true),
+            (vec!["add", "-y", "Rent", "310€", "1m"], false),
+        ];
+        for (args, expected) in &tests {
+            let matches = cli()
+                .try_get_matches_from([vec!["costoflife"], args.clone()].concat())
+                .unwrap();
+            assert_eq!(needs_transactions(&matches), *expected, "{:?}", args);
+        }
+        // every command is covered, the completions do not load the data
+        for c in cli().get_subcommands() {
+            assert!(
+                c.get_name() == "completions"
+                    || tests
+                        .iter()
+                        .any(|(args, _)| args.first() == Some(&c.get_name())),
+                "{}",
+                c.get_name()
+            );
+        }
+        // the tables are empty
+        for p in [
+            summary_table(&ds, &d, None, &Period::Day, &TagFilter::default()),
+            tags_table(&ds, &d, false),
+            tags_table(&ds, &d, true),
+        ] {
+            assert_eq!(p.data.len(), 3);
+            assert!(!p.to_string().contains("NaN"));
+        }
+        assert_eq!(ds.cost_of_life(&d), 0.0);
+        // a total that rounds to zero
+        let mut ds = DataStore::new();
+        ds.insert_str("Gum 0.01€ 1y 010121 #food").unwrap();
+        assert_eq!(ds.cost_of_life(&d), 0.0);
+        let printed = tags_table(&ds, &d, false).ascii(true).to_string();
+        assert!(printed.contains("|0.00"), "{}", printed);
+        assert!(!printed.contains("NaN"), "{}", printed);
+        // the invalid percentages
+        let mut p = Printer::new(vec![5, 10]);
+        p.row(vec![Pcent(f32::NAN), Pcent(f32::INFINITY)]);
+        assert_eq!(p.to_string(), "0.00|0.00");
+    }
+
+    #[test]
+    fn test_status_line() {
+        let mut ds = DataStore::new();
+        let d = NaiveDate::from_ymd(2021, 1, 10);
+        assert_eq!(
+            status_line(&ds, &d, 30),
+            "CoL: 0.00€ | 0 active | 0 expiring"
+        );
+        ds.insert_str("Rent 310€ 1m 010121").unwrap();
+        ds.insert_str("Gym 70€ 1w 080121").unwrap();
+        ds.insert_str("Car 3650€ 1y 010121").unwrap();
+        ds.insert_str("Trip 100€ 1w 010221").unwrap();
+        assert_eq!(
+            status_line(&ds, &d, 30),
+            "CoL: 30.00€ | 3 active | 2 expiring"
+        );
+        assert_eq!(
+            status_line(&ds, &d, 1),
+            "CoL: 30.00€ | 3 active | 0 expiring"
+        );
     }
 }
@@ -1,11 +1,48 @@
-use bigdecimal::BigDecimal;
-use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, Weekday};
 use std::str::FromStr;
 
+/// The currency used when none is specified
+pub const DEFAULT_CURRENCY: &str = "EUR";
+/// The generic currency sign, it stands for the default currency
+pub const GENERIC_CURRENCY: &str = "¤";
+
 pub fn parse_amount(s: &str) -> Option<BigDecimal> {
     BigDecimal::from_str(s).ok()
 }
 
+/// Convert an amount to f32 without panicking
+///
+/// Amounts that do not fit in a f32 are converted
+/// to positive or negative infinity
+pub fn to_f32_safe(v: &BigDecimal) -> f32 {
+    match v.to_f32() {
+        Some(f) if !f.is_nan() => f,
+        _ if *v < BigDecimal::zero() => f32::NEG_INFINITY,
+        _ => f32::INFINITY,
+    }
+}
+
+/// Convert an amount to f64 without panicking
+///
+/// Amounts that do not fit in a f64 are converted
+/// to positive or negative infinity
+pub fn to_f64_safe(v: &BigDecimal) -> f64 {
+    match v.to_f64() {
+        Some(f) if !f.is_nan() => f,
+        _ if *v < BigDecimal::zero() => f64::NEG_INFINITY,
+        _ => f64::INFINITY,
+    }
+}
+
+/// Builds an amount from minor units (eg. cents) of a currency
+///
+/// The number of decimals is the one of the currency ISO 4217 code,
+/// eg. `1999` is `19.99` for `EUR` and `1999` for `JPY`
+pub fn parse_amount_cents(cents: i64, currency: &str) -> BigDecimal {
+    BigDecimal::new(cents.into(), currency_exponent(currency))
+}
+
 /// Returns the current date
 pub fn today() -> NaiveDate {
     Local::today().naive_utc()
@@ -16,6 +53,14 @@ pub fn now_local() -> DateTime<FixedOffset> {
     DateTime::from(Local::now())
 }
 
+/// Returns the current time with the local timezone moved to a date
+///
+/// It is the same as `now_local` when the date is today
+pub fn now_local_on(d: &NaiveDate) -> DateTime<FixedOffset> {
+    let now = now_local();
+    now + Duration::days((*d - now.naive_local().date()).num_days())
+}
+
 /// Builds a date from day/month/year numeric
 ///
 /// # Examples
@@ -30,6 +75,33 @@ pub fn date(d: u32, m: u32, y: i32) -> NaiveDate {
     NaiveDate::from_ymd(y, m, d)
 }
 
+/// Get the date of the nth weekday of a month (eg. the 1st monday)
+///
+/// Returns None if the month does not have the nth weekday
+/// (eg. the 5th monday) or if n is 0
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Weekday;
+/// use costoflife;
+///
+/// let d = costoflife::nth_weekday(2021, 3, Weekday::Mon, 1); // 2021-03-01
+/// assert_eq!(d, Some(costoflife::date(1, 3, 2021)));
+/// ```
+pub fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> Option<NaiveDate> {
+    if n == 0 {
+        return None;
+    }
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let offset = (7 + weekday.num_days_from_monday() - first.weekday().num_days_from_monday()) % 7;
+    let d = first + Duration::days((offset + 7 * (n - 1)) as i64);
+    match d.month() == month {
+        true => Some(d),
+        false => None,
+    }
+}
+
 /// Parse a date from string, it recognizes the formats
 ///
 /// - dd/mm/yyyy
@@ -50,6 +122,91 @@ pub fn date_from_str(s: &str) -> Option<NaiveDate> {
     None
 }
 
+/// The ISO 4217 codes of the currencies in circulation
+const CURRENCY_CODES: [&str; 155] = [
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT",
+    "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD", "CAD",
+    "CDF", "CHF", "CLP", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ",
+    "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IQD", "IRR", "ISK", "JMD", "JOD",
+    "JPY", "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT", "LAK", "LBP", "LKR",
+    "LRD", "LSL", "LYD", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR",
+    "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "OMR", "PAB", "PEN",
+    "PGK", "PHP", "PKR", "PLN", "PYG", "QAR", "RON", "RSD", "RUB", "RWF", "SAR", "SBD", "SCR",
+    "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "UGX", "USD", "UYU", "UZS",
+    "VES", "VND", "VUV", "WST", "XAF", "XCD", "XOF", "XPF", "YER", "ZAR", "ZMW", "ZWL",
+];
+
+/// Tells if a code is the ISO 4217 code of a currency in circulation
+///
+/// Used to tell an amount in a currency (eg. `10BHD`) from
+/// other tokens made of digits and capitals (eg. `2BED`)
+pub fn is_currency_code(code: &str) -> bool {
+    CURRENCY_CODES.contains(&code)
+}
+
+/// Returns the ISO 4217 code for a currency symbol
+///
+/// Unknown symbols (and currency codes) are returned unchanged
+pub fn currency_code(symbol: &str) -> &str {
+    match symbol {
+        "€" => "EUR",
+        "$" => "USD",
+        "£" => "GBP",
+        "¥" => "JPY",
+        "₹" => "INR",
+        "₩" => "KRW",
+        "₽" => "RUB",
+        "₺" => "TRY",
+        "₪" => "ILS",
+        s => s,
+    }
+}
+
+/// Returns the symbol for an ISO 4217 currency code
+///
+/// If the currency has no known symbol the code is returned
+pub fn currency_symbol(code: &str) -> &str {
+    match code {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        "RUB" => "₽",
+        "TRY" => "₺",
+        "ILS" => "₪",
+        c => c,
+    }
+}
+
+/// Returns the number of decimal places (minor unit exponent)
+/// used by a currency, given its ISO 4217 code
+///
+/// Currencies not listed use the library default of 2 decimals
+pub fn currency_exponent(code: &str) -> i64 {
+    match code {
+        "JPY" | "KRW" | "ISK" | "CLP" | "VND" | "PYG" | "UGX" | "XAF" | "XOF" => 0,
+        "BHD" | "KWD" | "OMR" | "JOD" | "TND" | "LYD" | "IQD" => 3,
+        _ => super::SCALE,
+    }
+}
+
+/// Round an amount half away from zero to a number of decimals
+///
+/// `BigDecimal::round` panics on long quotients (eg. a per diem),
+/// so half of the last decimal is added before truncating
+pub fn round_half_up(v: &BigDecimal, scale: i64) -> BigDecimal {
+    let half = BigDecimal::new(5.into(), scale + 1);
+    match *v < BigDecimal::zero() {
+        true => v - half,
+        false => v + half,
+    }
+    .with_scale(scale)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,4 +232,94 @@ mod tests {
         let r = date_from_str("30/01/2020");
         assert_eq!(r.unwrap(), date(30, 1, 2020));
     }
+
+    #[test]
+    fn test_to_float_safe() {
+        let v = parse_amount("19.5").unwrap();
+        assert_eq!(to_f32_safe(&v), 19.5);
+        assert_eq!(to_f64_safe(&v), 19.5);
+        // out of the f32 range
+        let huge = parse_amount("1e100").unwrap();
+        assert_eq!(to_f32_safe(&huge), f32::INFINITY);
+        assert_eq!(to_f32_safe(&-huge.clone()), f32::NEG_INFINITY);
+        assert!(to_f64_safe(&huge).is_finite());
+        // out of the f64 range
+        let huge = parse_amount("1e400").unwrap();
+        assert_eq!(to_f64_safe(&huge), f64::INFINITY);
+        assert_eq!(to_f64_safe(&-huge), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_nth_weekday() {
+        // march 2021 starts on monday
+        assert_eq!(
+            nth_weekday(2021, 3, Weekday::Mon, 1),
+            Some(date(1, 3, 2021))
+        );
+        assert_eq!(
+            nth_weekday(2021, 3, Weekday::Sun, 1),
+            Some(date(7, 3, 2021))
+        );
+        assert_eq!(
+            nth_weekday(2021, 3, Weekday::Mon, 2),
+            Some(date(8, 3, 2021))
+        );
+        assert_eq!(
+            nth_weekday(2021, 3, Weekday::Wed, 5),
+            Some(date(31, 3, 2021))
+        );
+        assert_eq!(nth_weekday(2021, 3, Weekday::Thu, 5), None);
+        // february 2021 has exactly 4 weeks
+        assert_eq!(
+            nth_weekday(2021, 2, Weekday::Sun, 4),
+            Some(date(28, 2, 2021))
+        );
+        assert_eq!(nth_weekday(2021, 2, Weekday::Mon, 5), None);
+        // invalid input
+        assert_eq!(nth_weekday(2021, 3, Weekday::Mon, 0), None);
+        assert_eq!(nth_weekday(2021, 13, Weekday::Mon, 1), None);
+    }
+
+    #[test]
+    fn test_currency() {
+        assert_eq!(currency_code("€"), "EUR");
+        assert_eq!(currency_code("¥"), "JPY");
+        assert_eq!(currency_code("BHD"), "BHD");
+        assert!(is_currency_code("BHD"));
+        assert!(is_currency_code("EUR"));
+        assert!(!is_currency_code("BED"));
+        assert!(!is_currency_code("PRO"));
+        assert!(!is_currency_code("eur"));
+        assert_eq!(currency_symbol("EUR"), "€");
+        assert_eq!(currency_symbol("BHD"), "BHD");
+        // minor units
+        assert_eq!(currency_exponent("EUR"), 2);
+        assert_eq!(currency_exponent("JPY"), 0);
+        assert_eq!(currency_exponent("BHD"), 3);
+        assert_eq!(currency_exponent("XYZ"), 2);
+        // amounts from minor units
+        assert_eq!(
+            parse_amount_cents(1999, "EUR"),
+            parse_amount("19.99").unwrap()
+        );
+        assert_eq!(parse_amount_cents(1999, "EUR").to_string(), "19.99");
+        assert_eq!(parse_amount_cents(5, "EUR").to_string(), "0.05");
+        assert_eq!(parse_amount_cents(1999, "JPY").to_string(), "1999");
+        assert_eq!(parse_amount_cents(1999, "BHD").to_string(), "1.999");
+        // rounding
+        let tests = vec![
+            ("9.994", 2, "9.99"),
+            ("9.995", 2, "10.00"),
+            ("-9.995", 2, "-10.00"),
+            ("-9.994", 2, "-9.99"),
+            ("980.5", 0, "981"),
+            ("1.2345", 3, "1.235"),
+            ("10", 2, "10.00"),
+            ("32.258064516129032258064516129032258", 2, "32.26"),
+        ];
+        for (v, scale, expected) in tests {
+            let r = round_half_up(&parse_amount(v).unwrap(), scale);
+            assert_eq!(r.to_string(), expected, "{} to {} decimals", v, scale);
+        }
+    }
 }